@@ -7,18 +7,41 @@ Supports all architectures: MIPS, ARM64, x86_64, and others.
 """
 
 import asyncio
+import base64
+import collections
+import copy
+import enum
+import errno
+import gzip
+import hashlib
+import hmac
+import http.client
+import io
+import itertools
 import json
 import logging
+import math
 import os
+import re
+import select
 import signal
+import ssl
+import struct
+import subprocess
 import sys
 import time
-from typing import Any, Dict, Optional
+import uuid
+import zlib
+from datetime import datetime, timezone
+from typing import Any, Dict, Optional, Tuple
 from urllib.error import URLError, HTTPError
-from urllib.parse import urljoin
-from urllib.request import Request, urlopen
+from urllib.parse import quote, urljoin, urlparse
+from urllib.request import Request, getproxies, proxy_bypass, urlopen
 import ipaddress
 import socket
+import threading
+from concurrent.futures import ThreadPoolExecutor, TimeoutError as FutureTimeoutError
+from http.server import BaseHTTPRequestHandler, ThreadingHTTPServer
 
 # Import Firebase RTDB client
 try:
@@ -26,17 +49,90 @@ try:
 except ImportError:
     FirebaseRealtimeDatabaseClient = None  # Will be handled gracefully below
 
+# Recent log lines kept for the telemetry `logTail` field
+_LOG_TAIL_LINES = 50
+_LOG_TAIL_TRUNCATED_MARKER = "...[truncated]"
+
+
+class LogTail(logging.Handler):
+    """Ring buffer of recent formatted log lines, read by each agent via a cursor.
+
+    Lines remember the thread that logged them, so in multi-printer mode each
+    agent can read back only its own printer's lines.
+    """
+
+    def __init__(self, capacity: int):
+        super().__init__()
+        self.lines: collections.deque = collections.deque(maxlen=capacity)
+        self.seq = 0
+
+    def emit(self, record: logging.LogRecord) -> None:
+        try:
+            line = self.format(record)
+        except Exception:
+            self.handleError(record)
+            return
+        self.seq += 1
+        self.lines.append((self.seq, record.threadName, line))
+
+    def since(self, cursor: int, thread: Optional[str] = None) -> Tuple[list, int]:
+        """Lines logged after `cursor` (only by `thread`, if given), and the cursor to pass next time."""
+        self.acquire()
+        try:
+            lines = [
+                line for seq, thread_name, line in self.lines
+                if seq > cursor and thread in (None, thread_name)
+            ]
+            return lines, self.seq
+        finally:
+            self.release()
+
+
+def clamp_log_tail(lines: list, max_bytes: int) -> list:
+    """Keep the most recent `max_bytes` (UTF-8) of `lines`.
+
+    Older lines are dropped first and the oldest survivor may be cut from
+    the front; either way it is prefixed with a truncation marker. A single
+    huge line (a long traceback) thus can't blow up the telemetry payload.
+    """
+    if sum(len(line.encode("utf-8")) for line in lines) <= max_bytes:
+        return lines
+    budget = max_bytes - len(_LOG_TAIL_TRUNCATED_MARKER)
+    kept = []
+    for line in reversed(lines):
+        data = line.encode("utf-8")
+        if len(data) <= budget:
+            kept.append(line)
+            budget -= len(data)
+            continue
+        if budget > 0:
+            kept.append(data[-budget:].decode("utf-8", errors="ignore"))
+        break
+    if not kept:
+        return [_LOG_TAIL_TRUNCATED_MARKER] if max_bytes >= len(_LOG_TAIL_TRUNCATED_MARKER) else []
+    kept.reverse()
+    kept[0] = _LOG_TAIL_TRUNCATED_MARKER + kept[0]
+    return kept
+
+
+LOG_TAIL = LogTail(_LOG_TAIL_LINES)
+
+
 # Setup logging
-def setup_logging(log_file: Optional[str] = None) -> None:
+def setup_logging(log_file: Optional[str] = None, with_thread: bool = False) -> None:
     """Configure logging.
 
     If REACH_LINK_LOG_FILE is set, log only to that file (the init script's
     stdout redirect would double-write every line if we also kept a console
     handler pointing at the same file).  Without a log_file we log to stdout
     so that the shell redirect in the init script works as expected.
+    `with_thread` adds the thread name, which identifies the printer in
+    multi-printer mode.
     """
     log_level = logging.INFO
     log_format = "%(asctime)s [%(levelname)s] %(message)s"
+    if with_thread:
+        log_format = "%(asctime)s [%(levelname)s] [%(threadName)s] %(message)s"
     formatter = logging.Formatter(log_format)
 
     root = logging.getLogger()
@@ -55,8 +151,136 @@ def setup_logging(log_file: Optional[str] = None) -> None:
     handler.setFormatter(formatter)
     root.addHandler(handler)
 
+    LOG_TAIL.setFormatter(formatter)
+    root.addHandler(LOG_TAIL)
+
+
+# Levels accepted by POST /log-level
+_LOG_LEVELS = ("debug", "info", "warning", "error", "critical")
+
+
+def set_log_level(request: Dict[str, Any]) -> Dict[str, Any]:
+    """Change the process log level at runtime (health server POST /log-level)."""
+    name = str(request.get("level") or "").strip().lower()
+    if name not in _LOG_LEVELS:
+        raise ValueError(f"level must be one of: {', '.join(_LOG_LEVELS)}")
+    level = getattr(logging, name.upper())
+    root = logging.getLogger()
+    root.setLevel(level)
+    for handler in root.handlers:
+        handler.setLevel(level)
+    logger.warning(f"Log level set to {name}")
+    return {"level": name}
+
+
 logger = logging.getLogger(__name__)
 AGENT_VERSION = "1.0.12"
+# Git commit of this build; the release workflow stamps it into the published
+# script. A plain checkout asks git instead (see agent_commit).
+BUILD_COMMIT = "unknown"
+
+# Upper bound on one system health collection before it's reported as None
+_SYSTEM_HEALTH_TIMEOUT_SECS = 2
+
+# Relay WebSocket stream endpoint and maximum reconnect backoff
+_WS_STREAM_PATH = "/api/reach-link/stream"
+_WS_MAX_BACKOFF_SECS = 300
+
+# Fixed spacing between REACH_LINK_RELAY_RETRIES attempts
+_RELAY_RETRY_DELAY_SECS = 1.0
+
+# Refresh cadence of the lifetime job totals (`stats`); they change slowly
+_JOB_STATS_INTERVAL_SECS = 600
+
+# Cadence of the between-cycles Klippy error probe (see ErrorQueue)
+_ERROR_POLL_SECS = 5
+
+# Floor for the watchdog's stall limit (REACH_LINK_WATCHDOG_MULTIPLIER × interval)
+_WATCHDOG_MIN_SECS = 120
+_WATCHDOG_CHECK_SECS = 10
+
+# Heartbeat retry cadence until the first registration after boot succeeds
+_STARTUP_REGISTER_RETRY_SECS = 30
+
+# Upper bound on REACH_LINK_SHUTDOWN_GCODE so it can never hang the exit
+_SHUTDOWN_GCODE_TIMEOUT_SECS = 5
+
+# Actions accepted by the `power` remote command (Moonraker device_power API)
+_POWER_ACTIONS = ("on", "off", "toggle")
+
+# Limits on the `query` remote command (ad-hoc Moonraker object passthrough)
+_QUERY_MAX_OBJECTS = 20
+_QUERY_MAX_RESPONSE_BYTES = 64 * 1024
+
+
+class ExitReason(enum.Enum):
+    """Why the agent process exited, each with its own exit status.
+
+    0 is reserved for a requested stop. Under systemd's Restart=on-failure
+    every other code triggers a restart, except those listed in the unit's
+    RestartPreventExitStatus= (revoked token, duplicate instance, uninstall),
+    where restarting can't help.
+    """
+
+    STOPPED = (0, "stopped by signal or interrupt")
+    FATAL_ERROR = (1, "unexpected fatal error")
+    CONFIG_ERROR = (2, "invalid configuration")
+    HEALTH_BIND_FAILED = (3, "health server port could not be bound")
+    WATCHDOG = (4, "watchdog found the agent loop stalled")
+    ONCE_FAILED = (5, "--once registration or telemetry failed")
+    TOKEN_REVOKED = (6, "token revoked by the relay")
+    ALREADY_RUNNING = (7, "another agent instance is already running")
+    UPDATED = (8, "updated to a new version; restart to run it")
+    UNINSTALLED = (9, "uninstalled by remote command")
+
+    def __init__(self, code: int, description: str):
+        self.code = code
+        self.description = description
+
+
+# Callbacks (reason, detail) that tell the relay why we're exiting; main()
+# registers one per running agent. Together bounded by _EXIT_NOTICE_TIMEOUT_SECS.
+_EXIT_NOTIFIERS: list = []
+_EXIT_NOTICE_TIMEOUT_SECS = 3
+
+
+def _notify_exit(reason: ExitReason, detail: str) -> None:
+    """Run the exit notifiers in parallel, abandoning any still running at the deadline."""
+    threads = [
+        threading.Thread(target=notify, args=(reason, detail), name="exit-notice", daemon=True)
+        for notify in list(_EXIT_NOTIFIERS)
+    ]
+    for thread in threads:
+        thread.start()
+    deadline = time.monotonic() + _EXIT_NOTICE_TIMEOUT_SECS
+    for thread in threads:
+        thread.join(max(0.0, deadline - time.monotonic()))
+
+
+def exit_agent(reason: ExitReason, detail: str = "", hard: bool = False) -> None:
+    """Log one final `reason=... code=...` line and exit with the reason's status.
+
+    Registered agents first send the relay a disconnect notice carrying the
+    reason. `hard` exits immediately via os._exit, for threads (the watchdog,
+    the uninstall timer) where sys.exit would only end the thread.
+    """
+    message = f"Exiting: reason={reason.name.lower()} code={reason.code} ({reason.description})"
+    if detail:
+        message += f": {detail}"
+    _notify_exit(reason, detail)
+    if reason.code == 0:
+        logger.info(message)
+    else:
+        logger.error(message)
+    if hard:
+        logging.shutdown()
+        os._exit(reason.code)
+    sys.exit(reason.code)
+
+
+class HealthBindError(RuntimeError):
+    """The required health server port could not be bound."""
+
 
 # PID file used to prevent duplicate agent instances
 _PID_FILE = "/tmp/reach-link.pid"
@@ -123,6 +347,13 @@ def _release_pid_lock() -> None:
 # Configuration
 # ============================================================================
 
+# Accepted REACH_LINK_MIN_TLS values. Anything below 1.2 is deliberately absent
+# so regulated deployments can never be configured into a weaker handshake.
+_TLS_VERSIONS = {
+    "1.2": ssl.TLSVersion.TLSv1_2,
+    "1.3": ssl.TLSVersion.TLSv1_3,
+}
+
 class Config:
     """Load and validate configuration from environment."""
     
@@ -130,6 +361,8 @@ class Config:
         self._load_env_file()  # Load .env from script dir before reading any env vars
         self.relay_url = self._require_env("REACH_LINK_RELAY")
         self.token = os.environ.get("REACH_LINK_TOKEN", "").strip()
+        # The token as supplied, before any rotated token from the state file
+        self.configured_token = self.token
         self.pairing_code = os.environ.get("REACH_LINK_PAIRING_CODE", "").strip()
         self.state_file = os.environ.get("REACH_LINK_STATE_FILE", "./.reach-link-state.json").strip()
         self.printer_id = os.environ.get("REACH_LINK_PRINTER_ID", "").strip() or os.environ.get("REACH_PRINTER_ID", "").strip()
@@ -138,16 +371,83 @@ class Config:
         self.moonraker_url = os.environ.get(
             "REACH_LINK_MOONRAKER_URL", "http://127.0.0.1:7125"
         ).rstrip("/")
+        self.extra_objects = self._parse_extra_objects(
+            os.environ.get("REACH_LINK_EXTRA_OBJECTS", "")
+        )
+        # Printer make/model for the relay UI; auto-detected hints fill in blanks
+        self.printer_make = os.environ.get("REACH_LINK_PRINTER_MAKE", "").strip()
+        self.printer_model = os.environ.get("REACH_LINK_PRINTER_MODEL", "").strip()
+        # Grouping metadata sent once per registration
+        self.tags = self._parse_tags(os.environ.get("REACH_LINK_TAGS", ""))
+        # Multi-printer mode: a JSON file listing the printers this process serves
+        self.multi_printer = False
+        self.printers_file = os.environ.get("REACH_LINK_PRINTERS_FILE", "").strip()
+        self.printers = self._load_printers_file(self.printers_file) if self.printers_file else []
         self.heartbeat_interval = int(
             os.environ.get("REACH_LINK_HEARTBEAT_INTERVAL", "30")
         )
         self.telemetry_interval = int(
             os.environ.get("REACH_LINK_TELEMETRY_INTERVAL", "10")
         )
+        # Telemetry batching: samples per relay request, and the longest a
+        # partial batch may wait before it is sent anyway
+        self.batch_size = int(os.environ.get("REACH_LINK_BATCH_SIZE", "1"))
+        self.batch_max_age = int(os.environ.get("REACH_LINK_BATCH_MAX_AGE", "300"))
         self.command_poll_interval = int(
             os.environ.get("REACH_LINK_COMMAND_POLL_INTERVAL", "25")
         )
+        # Extra attempts for a register/telemetry POST hitting a 5xx or connection error
+        self.relay_retries = int(os.environ.get("REACH_LINK_RELAY_RETRIES", "2"))
+        # Bounds applied to relay-provided intervals (nextCheckIn / nextDataInterval)
+        self.min_interval = int(os.environ.get("REACH_LINK_MIN_INTERVAL", "2"))
+        self.max_interval = int(os.environ.get("REACH_LINK_MAX_INTERVAL", "3600"))
         self.log_file = os.environ.get("REACH_LINK_LOG_FILE")
+        self.user_agent = (
+            os.environ.get("REACH_LINK_USER_AGENT", "").strip() or f"reach-link/{AGENT_VERSION}"
+        )
+        # HTTP keep-alive pool. Agent requests recur every ~10-60s, so an idle
+        # connection is worth keeping for just under a minute: long enough to
+        # span the usual telemetry/heartbeat gap (saving a TLS handshake each
+        # cycle on the Pi) but below the common 60s load-balancer idle
+        # timeout, so we rarely pick up a socket the relay side already
+        # closed. Two idle sockets per host covers the main loop plus a
+        # concurrent command thread. REACH_LINK_POOL_IDLE_SECS=0 disables pooling.
+        self.pool_idle_secs = int(os.environ.get("REACH_LINK_POOL_IDLE_SECS", "55"))
+        self.pool_max_idle_per_host = int(
+            os.environ.get("REACH_LINK_POOL_MAX_IDLE_PER_HOST", "2")
+        )
+        self.min_tls = self._parse_min_tls(
+            os.environ.get("REACH_LINK_MIN_TLS", "1.2").strip()
+        )
+
+        # Optional relay auth-proxy settings
+        self.relay_basic_user = os.environ.get("REACH_LINK_RELAY_BASIC_USER", "")
+        self.relay_basic_pass = os.environ.get("REACH_LINK_RELAY_BASIC_PASS", "")
+        self.relay_headers = self._parse_relay_headers(os.environ)
+        # How register/telemetry payload timestamps are serialized
+        self.timestamp_format = os.environ.get("REACH_LINK_TIMESTAMP_FORMAT", "unix_ms").strip().lower()
+        # Optional shared secret for X-Reach-Signature request signing
+        self.hmac_secret = os.environ.get("REACH_LINK_HMAC_SECRET", "")
+        # Opt-in: stream telemetry over a persistent WebSocket instead of POSTs
+        self.relay_ws = os.environ.get("REACH_LINK_RELAY_WS", "").strip() == "1"
+        # Heartbeat on every Nth telemetry tick instead of its own interval (0 = off)
+        self.shared_cadence = int(os.environ.get("REACH_LINK_SHARED_CADENCE", "0"))
+        # Exit when the agent loop stalls for this many × the larger interval (0 disables)
+        self.watchdog_multiplier = int(os.environ.get("REACH_LINK_WATCHDOG_MULTIPLIER", "3"))
+        # Overall deadline for cleanup (batch flush, shutdown G-code, ...) on exit
+        self.shutdown_timeout = int(os.environ.get("REACH_LINK_SHUTDOWN_TIMEOUT", "15"))
+        # G-code run through Moonraker when the agent shuts down (e.g. "TURN_OFF_HEATERS")
+        self.shutdown_gcode = os.environ.get("REACH_LINK_SHUTDOWN_GCODE", "").strip()
+        # Send a single register + telemetry sample and exit (same as --once)
+        self.once = os.environ.get("REACH_LINK_ONCE", "").strip() == "1"
+
+        # Local health endpoint
+        self.health_port = int(os.environ.get("REACH_LINK_HEALTH_PORT", "8080"))
+        self.health_bind = self._parse_health_bind(
+            os.environ.get("REACH_LINK_HEALTH_BIND", "0.0.0.0").strip()
+        )
+        # Keep running (without /health) when the port can't be bound
+        self.health_optional = os.environ.get("REACH_LINK_HEALTH_OPTIONAL", "").strip() == "1"
         
         # Firebase RTDB configuration (optional, for cloud command queue)
         self.firebase_database_url = os.environ.get("REACH_LINK_FIREBASE_DATABASE_URL", "")
@@ -161,20 +461,95 @@ class Config:
             os.environ.get("REACH_LINK_WEBCAM_VIEWER_TIMEOUT", "60")
         )
 
+        # Minimum seconds between re-sends of an identical error
+        self.error_dedup_secs = int(os.environ.get("REACH_LINK_ERROR_DEDUP_SECS", "300"))
+        # Cap on the UTF-8 size of the telemetry logTail (most recent lines kept)
+        self.log_tail_max_bytes = int(os.environ.get("REACH_LINK_LOG_TAIL_MAX_BYTES", "8192"))
+        # Default length of maintenance (quiet) mode before it auto-expires
+        self.maintenance_secs = int(os.environ.get("REACH_LINK_MAINTENANCE_SECS", "3600"))
+
+        # Recent print history refresh (0 disables the history query)
+        self.history_interval = int(
+            os.environ.get("REACH_LINK_HISTORY_INTERVAL", "300")
+        )
+        # Active Spoolman spool refresh (0 disables the Spoolman query)
+        self.filament_interval = int(
+            os.environ.get("REACH_LINK_FILAMENT_INTERVAL", "300")
+        )
+
         self._load_persisted_state()
         
         # Validate
         if not self.relay_url.startswith("https://") and not self.relay_url.startswith("http://"):
             raise ValueError(f"REACH_LINK_RELAY must use HTTPS or HTTP, got: {self.relay_url}")
-        if not self.token and not self.pairing_code:
+        if not self.token and not self.pairing_code and not self.printers:
             raise ValueError(
                 "Bootstrap error: Neither REACH_LINK_TOKEN nor REACH_LINK_PAIRING_CODE is set.\n"
                 "First setup: Run the wizard in Reach3D dashboard to create a pairing session, "
                 "then run the setup command it provides.\n"
                 "Existing setup: Set REACH_LINK_TOKEN to the token saved during first setup."
             )
+        self._validate_moonraker_url(self.moonraker_url)
         if self.token and not self.printer_id:
             raise ValueError("REACH_LINK_PRINTER_ID must not be empty when REACH_LINK_TOKEN is used")
+        if self.pool_idle_secs < 0 or self.pool_max_idle_per_host < 1:
+            raise ValueError(
+                "REACH_LINK_POOL_IDLE_SECS must be >= 0 and REACH_LINK_POOL_MAX_IDLE_PER_HOST >= 1"
+            )
+        if self.timestamp_format not in ("unix_ms", "rfc3339"):
+            raise ValueError(
+                f"REACH_LINK_TIMESTAMP_FORMAT must be unix_ms or rfc3339, got: {self.timestamp_format}"
+            )
+        if self.relay_retries < 0:
+            raise ValueError(f"REACH_LINK_RELAY_RETRIES must be >= 0, got: {self.relay_retries}")
+        if self.shared_cadence < 0:
+            raise ValueError(f"REACH_LINK_SHARED_CADENCE must be >= 0, got: {self.shared_cadence}")
+        if self.watchdog_multiplier < 0:
+            raise ValueError(f"REACH_LINK_WATCHDOG_MULTIPLIER must be >= 0, got: {self.watchdog_multiplier}")
+        if self.shutdown_timeout < 1:
+            raise ValueError(f"REACH_LINK_SHUTDOWN_TIMEOUT must be >= 1, got: {self.shutdown_timeout}")
+        if self.log_tail_max_bytes < 0:
+            raise ValueError(f"REACH_LINK_LOG_TAIL_MAX_BYTES must be >= 0, got: {self.log_tail_max_bytes}")
+        if self.maintenance_secs < 1:
+            raise ValueError(f"REACH_LINK_MAINTENANCE_SECS must be >= 1, got: {self.maintenance_secs}")
+        if self.batch_size < 1:
+            raise ValueError(f"REACH_LINK_BATCH_SIZE must be >= 1, got: {self.batch_size}")
+        if self.min_interval < 1 or self.max_interval < self.min_interval:
+            raise ValueError(
+                "REACH_LINK_MIN_INTERVAL must be >= 1 and <= REACH_LINK_MAX_INTERVAL, "
+                f"got min={self.min_interval} max={self.max_interval}"
+            )
+        if self.relay_basic_pass and not self.relay_basic_user:
+            raise ValueError("REACH_LINK_RELAY_BASIC_PASS is set but REACH_LINK_RELAY_BASIC_USER is not")
+
+    def clamp_interval(self, name: str, value: Any) -> Optional[int]:
+        """Bound a relay-provided interval to [min_interval, max_interval].
+
+        Returns None for missing or non-positive values so callers keep their
+        current interval. A buggy relay can neither make us hammer it nor
+        effectively switch telemetry off.
+        """
+        if not isinstance(value, (int, float)) or isinstance(value, bool) or value <= 0:
+            return None
+        clamped = int(min(max(value, self.min_interval), self.max_interval))
+        if clamped != value:
+            logger.warning(
+                f"Relay requested {name}={value}s; clamped to {clamped}s "
+                f"(bounds {self.min_interval}-{self.max_interval}s)"
+            )
+        return clamped
+
+    @property
+    def health_addr(self) -> Tuple[str, int]:
+        """Address the /health server listens on."""
+        return (str(self.health_bind), self.health_port)
+
+    @property
+    def relay_basic_auth(self) -> Optional[Tuple[str, str]]:
+        """(user, password) for the relay auth proxy, or None when not configured."""
+        if not self.relay_basic_user:
+            return None
+        return (self.relay_basic_user, self.relay_basic_pass)
 
     def _load_persisted_state(self):
         """Load persisted bootstrap credentials from disk if available."""
@@ -182,14 +557,15 @@ class Config:
             return
 
         try:
-            if not os.path.exists(self.state_file):
+            data = self._read_state_file()
+            if data is None:
                 return
 
-            with open(self.state_file, "r", encoding="utf-8") as state_fp:
-                data = json.load(state_fp)
-
             if not self.token:
                 self.token = str(data.get("reachLinkToken", "") or data.get("token", "")).strip()
+            else:
+                # An exported REACH_LINK_TOKEN outlives .env rewrites after a rotation
+                self._adopt_rotated_token(data)
             if not self.printer_id:
                 self.printer_id = str(data.get("printerId", "") or data.get("reachLinkPrinterId", "")).strip()
             if not self.user_id:
@@ -201,6 +577,32 @@ class Config:
         except Exception as error:
             logger.warning(f"Failed to load persisted state file {self.state_file}: {error}")
 
+    def _read_state_file(self) -> Optional[Dict[str, Any]]:
+        """Parsed state file, or None when there is none."""
+        if not self.state_file or not os.path.exists(self.state_file):
+            return None
+        with open(self.state_file, "r", encoding="utf-8") as state_fp:
+            return json.load(state_fp)
+
+    def _adopt_rotated_token(self, data: Dict[str, Any]) -> bool:
+        """Switch to a rotated token from the state file over the configured one.
+
+        persist_state() records a fingerprint of the configured token the saved
+        one replaced. The saved token only wins while that configured token is
+        still the one supplied, so handing the agent a fresh token still works.
+        """
+        saved = str(data.get("reachLinkToken", "") or "").strip()
+        saved_printer = str(data.get("printerId", "") or "").strip()
+        if not saved or saved == self.token:
+            return False
+        if saved_printer and self.printer_id and saved_printer != self.printer_id:
+            return False
+        if data.get("replacesTokenSha256") != token_fingerprint(self.token):
+            return False
+        self.token = saved
+        logger.info(f"Using the rotated token from {self.state_file} over the configured one")
+        return True
+
     def persist_state(self):
         """Persist active credentials to disk for restart/reboot resilience."""
         if not self.state_file:
@@ -211,6 +613,8 @@ class Config:
             "printerId": self.printer_id,
             "userId": self.user_id,
             "relayUrl": self.relay_url,
+            "replacesTokenSha256": token_fingerprint(self.configured_token)
+            if self.configured_token and self.configured_token != self.token else None,
             "savedAt": int(time.time()),
         }
 
@@ -235,26 +639,134 @@ class Config:
         without manually re-exporting env vars every session.  Explicit env
         vars already set in the process take precedence over the file.
         """
-        script_dir = os.path.dirname(os.path.abspath(__file__))
-        env_path = os.path.join(script_dir, '.env')
+        # Keys taken from the file rather than exported, for reload_intervals()
+        self.env_file_keys: set = set()
+        env_path = self._env_file_path()
         if not os.path.exists(env_path):
             return
         try:
-            with open(env_path, 'r', encoding='utf-8') as f:
-                for line in f:
-                    line = line.strip()
-                    if not line or line.startswith('#') or '=' not in line:
-                        continue
-                    key, _, value = line.partition('=')
-                    key = key.strip()
-                    value = value.strip()
-                    # Only set if not already in the process environment
-                    if key and not os.environ.get(key):
-                        os.environ[key] = value
+            for key, value in self._read_env_file(env_path).items():
+                # Only set if not already in the process environment
+                if not os.environ.get(key):
+                    os.environ[key] = value
+                    self.env_file_keys.add(key)
             print(f"[reach-link] Loaded credentials from {env_path}", file=sys.stderr)
         except Exception as e:
             print(f"[reach-link] Warning: could not read {env_path}: {e}", file=sys.stderr)
 
+    def _load_printers_file(self, path: str) -> list:
+        """Load and validate REACH_LINK_PRINTERS_FILE.
+
+        Format: a JSON list of {"printerId", "token", "moonrakerUrl",
+        "stateFile"} objects. printerId and token are required (pairing isn't
+        supported in multi-printer mode); moonrakerUrl defaults to
+        REACH_LINK_MOONRAKER_URL; stateFile is optional and only needed to
+        persist rotated tokens.
+        """
+        try:
+            with open(path, "r", encoding="utf-8") as f:
+                entries = json.load(f)
+        except (OSError, ValueError) as e:
+            raise ValueError(f"Could not read REACH_LINK_PRINTERS_FILE {path}: {e}")
+        if not isinstance(entries, list) or not entries:
+            raise ValueError(f"REACH_LINK_PRINTERS_FILE {path} must contain a non-empty JSON list")
+
+        printers = []
+        seen = set()
+        for index, entry in enumerate(entries):
+            if not isinstance(entry, dict):
+                raise ValueError(f"REACH_LINK_PRINTERS_FILE entry {index} must be an object")
+            printer_id = str(entry.get("printerId", "")).strip()
+            token = str(entry.get("token", "")).strip()
+            if not printer_id or not token:
+                raise ValueError(f"REACH_LINK_PRINTERS_FILE entry {index} needs printerId and token")
+            if printer_id in seen:
+                raise ValueError(f"REACH_LINK_PRINTERS_FILE lists printer {printer_id} twice")
+            seen.add(printer_id)
+            moonraker_url = str(entry.get("moonrakerUrl") or self.moonraker_url).rstrip("/")
+            self._validate_moonraker_url(
+                moonraker_url, f"REACH_LINK_PRINTERS_FILE entry {index} moonrakerUrl"
+            )
+            printers.append({
+                "printerId": printer_id,
+                "token": token,
+                "moonrakerUrl": moonraker_url,
+                "stateFile": str(entry.get("stateFile", "")).strip(),
+            })
+        return printers
+
+    def for_printer(self, entry: Dict[str, str]) -> "Config":
+        """Per-printer copy of this config for multi-printer mode."""
+        config = copy.copy(self)
+        config.printer_id = entry["printerId"]
+        config.token = entry["token"]
+        config.configured_token = entry["token"]
+        config.moonraker_url = entry["moonrakerUrl"]
+        config.state_file = entry["stateFile"]
+        config.printers = []
+        config.multi_printer = True
+        # .env can't carry a fleet printer's rotated token; its state file does
+        try:
+            data = config._read_state_file()
+        except (OSError, ValueError) as error:
+            logger.warning(f"Failed to load persisted state file {config.state_file}: {error}")
+            data = None
+        if data:
+            config._adopt_rotated_token(data)
+        return config
+
+    @staticmethod
+    def _env_file_path() -> str:
+        return os.path.join(os.path.dirname(os.path.abspath(__file__)), '.env')
+
+    @staticmethod
+    def _read_env_file(env_path: str) -> Dict[str, str]:
+        """Parse KEY=VALUE lines from a .env file, skipping blanks and comments."""
+        values: Dict[str, str] = {}
+        with open(env_path, 'r', encoding='utf-8') as f:
+            for line in f:
+                line = line.strip()
+                if not line or line.startswith('#') or '=' not in line:
+                    continue
+                key, _, value = line.partition('=')
+                key = key.strip()
+                if key:
+                    values[key] = value.strip()
+        return values
+
+    def reload_intervals(self) -> Dict[str, int]:
+        """Re-read the reloadable interval settings (SIGHUP).
+
+        The process environment can't change under a running agent, so new
+        values come from the .env file. Precedence matches startup: a
+        variable exported in the environment still wins over the file, so
+        only settings that came from (or are new in) .env can change. Only
+        the heartbeat and telemetry intervals are reloadable; relay URL, token and everything else need a
+        restart. Returns the fields that changed, mapped to their new values.
+        """
+        env_path = self._env_file_path()
+        file_values = self._read_env_file(env_path) if os.path.exists(env_path) else {}
+        changed: Dict[str, int] = {}
+        for attr, name in (
+            ("heartbeat_interval", "REACH_LINK_HEARTBEAT_INTERVAL"),
+            ("telemetry_interval", "REACH_LINK_TELEMETRY_INTERVAL"),
+        ):
+            if os.environ.get(name) and name not in self.env_file_keys:
+                raw = os.environ[name]
+            else:
+                raw = file_values.get(name)
+            if not raw:
+                continue
+            try:
+                value = int(raw)
+            except ValueError:
+                logger.warning(f"Ignoring invalid {name}={raw!r} on reload")
+                continue
+            if value > 0 and value != getattr(self, attr):
+                setattr(self, attr, value)
+                changed[attr] = value
+        return changed
+
     def _write_env_file(self) -> None:
         """Write current credentials back to .env in the agent's directory.
 
@@ -265,9 +777,10 @@ class Config:
         """
         if not (self.token and self.printer_id):
             return  # Don't write an incomplete .env
+        if self.multi_printer:
+            return  # One .env can't hold several printers' credentials
 
-        script_dir = os.path.dirname(os.path.abspath(__file__))
-        env_path = os.path.join(script_dir, '.env')
+        env_path = self._env_file_path()
 
         managed_keys = {
             'REACH_LINK_RELAY', 'REACH_LINK_TOKEN', 'REACH_LINK_PRINTER_ID',
@@ -309,6 +822,83 @@ class Config:
         except Exception as e:
             logger.warning(f"Could not write .env file {env_path}: {e}")
 
+    @staticmethod
+    def _parse_min_tls(value: str) -> str:
+        """Validate REACH_LINK_MIN_TLS; only TLS 1.2 and 1.3 are acceptable floors."""
+        if value not in _TLS_VERSIONS:
+            raise ValueError(
+                f"REACH_LINK_MIN_TLS must be one of {', '.join(sorted(_TLS_VERSIONS))}, got: {value!r}"
+            )
+        return value
+
+    @staticmethod
+    def _parse_extra_objects(value: str) -> list:
+        """Parse REACH_LINK_EXTRA_OBJECTS: comma-separated Moonraker object names."""
+        if not value.strip():
+            return []
+        names = [name.strip() for name in value.split(",")]
+        if any(not name for name in names):
+            raise ValueError(f"REACH_LINK_EXTRA_OBJECTS contains an empty entry: {value!r}")
+        return names
+
+    @staticmethod
+    def _validate_moonraker_url(url: str, name: str = "REACH_LINK_MOONRAKER_URL") -> None:
+        """Reject a Moonraker URL without an http(s) scheme and host (e.g. `htpp://`, `printer:7125`)."""
+        parsed = urlparse(url)
+        if parsed.scheme not in ("http", "https") or not parsed.hostname:
+            raise ValueError(f"{name} must be an http:// or https:// URL with a host, got: {url!r}")
+
+    @staticmethod
+    def _parse_tags(value: str) -> Dict[str, str]:
+        """Parse REACH_LINK_TAGS: comma-separated key=value pairs (e.g. site=lab,owner=ops)."""
+        tags: Dict[str, str] = {}
+        for entry in value.split(","):
+            if not entry.strip():
+                continue
+            key, sep, tag_value = entry.partition("=")
+            if not sep or not key.strip():
+                raise ValueError(
+                    f"REACH_LINK_TAGS entries must be key=value with a non-empty key, got: {entry!r}"
+                )
+            tags[key.strip()] = tag_value.strip()
+        return tags
+
+    @staticmethod
+    def _parse_health_bind(value: str):
+        """Parse REACH_LINK_HEALTH_BIND as an IP address (e.g. 127.0.0.1 for local-only)."""
+        try:
+            return ipaddress.ip_address(value)
+        except ValueError:
+            raise ValueError(f"REACH_LINK_HEALTH_BIND must be an IP address, got: {value!r}")
+
+    @staticmethod
+    def _parse_relay_headers(environ) -> Dict[str, str]:
+        """Collect static relay headers from REACH_LINK_RELAY_HEADER[_<suffix>].
+
+        Env vars can't repeat, so each extra header gets its own variable
+        (REACH_LINK_RELAY_HEADER, REACH_LINK_RELAY_HEADER_1, ...), each holding
+        one "Key: Value" pair.
+        """
+        headers: Dict[str, str] = {}
+        for name in sorted(environ):
+            if name != "REACH_LINK_RELAY_HEADER" and not name.startswith("REACH_LINK_RELAY_HEADER_"):
+                continue
+            raw = environ[name].strip()
+            key, sep, value = raw.partition(":")
+            key = key.strip()
+            value = value.strip()
+            if (
+                not sep
+                or not key
+                or not value
+                or any(c.isspace() or c in '()<>@,;:\\"/[]?={}' for c in key)
+                or "\r" in value
+                or "\n" in value
+            ):
+                raise ValueError(f"{name} must be a 'Key: Value' header, got: {raw!r}")
+            headers[key] = value
+        return headers
+
     @staticmethod
     def _require_env(name: str) -> str:
         """Get required environment variable."""
@@ -375,55 +965,308 @@ class SubnetDetector:
 # HTTP Client (stdlib-only, no external dependencies)
 # ============================================================================
 
+class PooledResponse:
+    """Fully-read response from ConnectionPool, shaped like urlopen()'s result."""
+
+    def __init__(self, status: int, reason: str, headers, body: bytes):
+        self.status = status
+        self.reason = reason
+        self.headers = headers
+        self._body = io.BytesIO(body)
+
+    def read(self, *args) -> bytes:
+        return self._body.read(*args)
+
+    def __enter__(self):
+        return self
+
+    def __exit__(self, *exc) -> None:
+        self._body.close()
+
+
+class ConnectionPool:
+    """Keep-alive connection reuse for urllib-style requests.
+
+    urllib opens a fresh connection (and TLS handshake) per request; on a Pi
+    posting every few seconds that handshake dominates CPU. Idle connections
+    are kept per (scheme, host, port) for `idle_secs`, at most
+    `max_idle_per_host` of them. A reused socket the server already closed is
+    retried once on a fresh connection. Errors surface as HTTPError/URLError
+    like urlopen() so callers' handling is unchanged.
+    """
+
+    _REDIRECTS = (301, 302, 303, 307, 308)
+
+    def __init__(self, idle_secs: int, max_idle_per_host: int):
+        self.idle_secs = idle_secs
+        self.max_idle_per_host = max_idle_per_host
+        self._idle: Dict[Tuple[str, str, int], list] = {}
+        self._lock = threading.Lock()
+
+    def request(self, req: Request, timeout: int, context: Optional[ssl.SSLContext]):
+        parsed = urlparse(req.full_url)
+        secure = parsed.scheme == "https"
+        key = (parsed.scheme, parsed.hostname or "", parsed.port or (443 if secure else 80))
+        selector = req.selector or "/"
+        headers = dict(req.header_items())
+        headers["Connection"] = "keep-alive"
+
+        conn, reused = self._checkout(key)
+        for attempt in (1, 2):
+            if conn is None:
+                if secure:
+                    conn = http.client.HTTPSConnection(key[1], key[2], timeout=timeout, context=context)
+                else:
+                    conn = http.client.HTTPConnection(key[1], key[2], timeout=timeout)
+            conn.timeout = timeout
+            if conn.sock:
+                conn.sock.settimeout(timeout)
+            try:
+                conn.request(req.get_method(), selector, body=req.data, headers=headers)
+                response = conn.getresponse()
+                body = response.read()
+                break
+            except (http.client.RemoteDisconnected, ConnectionResetError, BrokenPipeError) as e:
+                conn.close()
+                conn = None
+                if not reused or attempt == 2:
+                    raise URLError(e)
+                reused = False  # stale keep-alive socket; retry on a fresh one
+            except http.client.HTTPException as e:
+                conn.close()
+                raise URLError(e)
+            except OSError:
+                conn.close()
+                raise
+
+        if response.will_close:
+            conn.close()
+        else:
+            self._checkin(key, conn)
+
+        if response.status in self._REDIRECTS:
+            # Rare for our endpoints; let urllib's redirect handling deal with it.
+            return urlopen(req, timeout=timeout, context=context)
+        if response.status >= 400:
+            raise HTTPError(req.full_url, response.status, response.reason, response.headers, io.BytesIO(body))
+        return PooledResponse(response.status, response.reason, response.headers, body)
+
+    def _checkout(self, key) -> Tuple[Optional[http.client.HTTPConnection], bool]:
+        now = time.monotonic()
+        with self._lock:
+            idle = self._idle.get(key, [])
+            while idle:
+                conn, idle_since = idle.pop()
+                if now - idle_since < self.idle_secs:
+                    return conn, True
+                conn.close()
+        return None, False
+
+    def _checkin(self, key, conn) -> None:
+        with self._lock:
+            idle = self._idle.setdefault(key, [])
+            if len(idle) < self.max_idle_per_host:
+                idle.append((conn, time.monotonic()))
+                return
+        conn.close()
+
+
+def sign_request(secret: str, timestamp: str, body: bytes) -> str:
+    """Hex HMAC-SHA256 over `<timestamp>.<body>` for the X-Reach-Signature header.
+
+    `body` must be the exact bytes sent on the wire (no re-serialization), and
+    `timestamp` the X-Reach-Timestamp header value (unix seconds), so the relay
+    can verify the body and reject stale or replayed requests.
+    """
+    message = timestamp.encode("ascii") + b"." + body
+    return hmac.new(secret.encode("utf-8"), message, hashlib.sha256).hexdigest()
+
+
+def token_fingerprint(token: str) -> str:
+    """SHA-256 of a token, so the state file can name one without holding it."""
+    return hashlib.sha256(token.encode("utf-8")).hexdigest()
+
+
+def signature_headers(secret: str, body: bytes) -> Dict[str, str]:
+    """X-Reach-Timestamp/X-Reach-Signature for `body`, stamped now."""
+    timestamp = str(int(time.time()))
+    return {
+        "X-Reach-Timestamp": timestamp,
+        "X-Reach-Signature": sign_request(secret, timestamp, body),
+    }
+
+
+# classify_http_error() kind -> one-line triage hint ({host} is the target).
+_HTTP_ERROR_HINTS = {
+    "dns": "check that {host} resolves (REACH_LINK_RELAY / REACH_LINK_MOONRAKER_URL, /etc/resolv.conf)",
+    "tls": "check the certificate for {host}, the system clock and REACH_LINK_MIN_TLS",
+    "refused": "nothing is listening on {host}; check the URL's port and that the service is up",
+    "timeout": "{host} did not answer in time; check the network path and any firewall",
+    "reset": "{host} dropped the connection; check for a proxy or load balancer in between",
+    "unreachable": "no route to {host}; check the printer's network link",
+    "client_error": "{host} rejected the request; check the token and printer ID",
+    "server_error": "{host} is failing; retry later or check the relay's status",
+    "unknown": "see the error text for {host}",
+}
+
+
+def classify_http_error(error: BaseException) -> str:
+    """Map a urllib/socket failure to a coarse kind for field triage.
+
+    Kinds are the keys of _HTTP_ERROR_HINTS. URLError wraps the underlying
+    socket error in `reason`, so that is inspected first.
+    """
+    if isinstance(error, HTTPError):
+        return "client_error" if 400 <= error.code < 500 else "server_error"
+    if isinstance(error, URLError) and isinstance(error.reason, BaseException):
+        error = error.reason
+    if isinstance(error, socket.gaierror):
+        return "dns"
+    if isinstance(error, ssl.SSLError):
+        return "tls"
+    if isinstance(error, ConnectionRefusedError):
+        return "refused"
+    if isinstance(error, (socket.timeout, TimeoutError)):
+        return "timeout"
+    if isinstance(error, (ConnectionResetError, BrokenPipeError, http.client.RemoteDisconnected)):
+        return "reset"
+    if isinstance(error, OSError) and error.errno in (errno.ENETUNREACH, errno.EHOSTUNREACH):
+        return "unreachable"
+    if isinstance(error, URLError) and "timed out" in str(error.reason):
+        return "timeout"
+    return "unknown"
+
+
+def describe_http_error(url: str, error: BaseException) -> str:
+    """`error_kind=<kind> <error> (hint: ...)` for a request's final failure log."""
+    kind = classify_http_error(error)
+    hint = _HTTP_ERROR_HINTS[kind].format(host=urlparse(url).netloc or url)
+    return f"error_kind={kind} {error} (hint: {hint})"
+
+
 class HTTPClient:
     """Simple HTTP client using urllib."""
+
+    # Shared TLS context and User-Agent for every outbound request (relay and
+    # Moonraker); replaced by configure() once the Config is loaded.
+    ssl_context: Optional[ssl.SSLContext] = None
+    user_agent = f"reach-link/{AGENT_VERSION}"
+    pool: Optional[ConnectionPool] = None
+
+    @staticmethod
+    def configure(config: "Config") -> None:
+        """Apply client-wide settings (TLS floor, User-Agent) from the loaded config."""
+        context = ssl.create_default_context()
+        context.minimum_version = _TLS_VERSIONS[config.min_tls]
+        HTTPClient.ssl_context = context
+        HTTPClient.user_agent = config.user_agent
+        HTTPClient.pool = (
+            ConnectionPool(config.pool_idle_secs, config.pool_max_idle_per_host)
+            if config.pool_idle_secs > 0 else None
+        )
+
+    @staticmethod
+    def open(req, timeout: int):
+        """urlopen() with the shared TLS context, User-Agent and keep-alive pool applied.
+
+        The pool connects directly, so requests that HTTP(S)_PROXY/no_proxy
+        route through a proxy bypass it and go through urlopen() instead.
+        """
+        if not isinstance(req, Request):
+            req = Request(req)
+        if not req.has_header("User-agent"):
+            req.add_header("User-Agent", HTTPClient.user_agent)
+        if HTTPClient.pool and not HTTPClient.is_proxied(req.full_url):
+            return HTTPClient.pool.request(req, timeout, HTTPClient.ssl_context)
+        return urlopen(req, timeout=timeout, context=HTTPClient.ssl_context)
     
+    @staticmethod
+    def is_proxied(url: str) -> bool:
+        """True if urllib would send `url` through a proxy from the environment."""
+        parsed = urlparse(url)
+        if parsed.scheme not in getproxies():
+            return False
+        return not proxy_bypass(parsed.hostname or "")
+
     @staticmethod
     def post_json(
         url: str,
-        data: Dict[str, Any],
+        data: Any,
         token: Optional[str] = None,
         timeout: int = 10,
         max_retries: int = 3,
+        basic_auth: Optional[Tuple[str, str]] = None,
+        extra_headers: Optional[Dict[str, str]] = None,
+        retry_delay: Optional[float] = None,
+        hmac_secret: Optional[str] = None,
+        request_id: Optional[str] = None,
     ) -> Optional[Dict[str, Any]]:
-        """POST JSON data with Bearer token auth; retry on failure."""
+        """POST JSON data with Bearer token auth; retry on failure.
+
+        Connection errors and 5xx responses are retried, backing off
+        exponentially unless `retry_delay` gives a fixed spacing; 4xx
+        responses are not. A 401 raises ValueError("TOKEN_REVOKED") only when
+        `token` was sent and the response isn't an auth proxy's challenge.
+        When `basic_auth` is given, HTTP Basic credentials take Authorization
+        and the token moves to X-Reach-Token (see auth_headers). When `hmac_secret` is given, each attempt is signed with a fresh
+        timestamp (see sign_request). `request_id` is sent as X-Request-Id
+        (the same id on every retry) and tagged onto this request's log lines.
+        """
         headers = {"Content-Type": "application/json"}
-        if token:
-            headers["Authorization"] = f"Bearer {token}"
+        if extra_headers:
+            headers.update(extra_headers)
+        if request_id:
+            headers["X-Request-Id"] = request_id
+        headers.update(HTTPClient.auth_headers(token, basic_auth))
         body = json.dumps(data).encode("utf-8")
         
         last_error = None
         for attempt in range(max_retries):
+            started = time.monotonic()
             try:
+                if hmac_secret:
+                    headers.update(signature_headers(hmac_secret, body))
                 req = Request(url, data=body, headers=headers, method="POST")
-                with urlopen(req, timeout=timeout) as response:
-                    response_body = response.read().decode("utf-8")
+                with HTTPClient.open(req, timeout=timeout) as response:
+                    response_body = HTTPClient.read_body(response).decode("utf-8")
+                    HTTPClient.log_duration(
+                        "POST", url, getattr(response, "status", None), started,
+                        request_id, response.headers.get("X-Request-Id"),
+                    )
                     if response_body:
                         return json.loads(response_body)
                     return None
             except HTTPError as e:
+                HTTPClient.log_duration(
+                    "POST", url, e.code, started,
+                    request_id, e.headers.get("X-Request-Id") if e.headers else None,
+                )
+                e.close()  # release the connection; only the status and headers are used
                 # 401 = token revoked; 403 = invalid token; 404 = not found.
-                # None of these will succeed on retry — break immediately.
-                if e.code == 401:
+                # No 4xx will succeed on retry — break immediately. A 401
+                # carrying a Basic challenge is the auth proxy rejecting its
+                # credentials, not the relay revoking our token.
+                if e.code == 401 and token and not HTTPClient.is_basic_challenge(e):
                     logger.error(f"Token revocation detected (HTTP 401): {e.reason}")
                     raise ValueError("TOKEN_REVOKED")
-                if e.code in (403, 404):
-                    logger.warning(f"HTTP POST received {e.code} (no retry): {e.reason}")
+                if 400 <= e.code < 500:
+                    logger.debug(f"HTTP POST received {e.code} (no retry): {e.reason}")
                     last_error = e
                     break
 
                 last_error = e
                 if attempt < max_retries - 1:
-                    wait = 2 ** attempt
+                    wait = retry_delay if retry_delay is not None else 2 ** attempt
                     logger.debug(
                         f"HTTP POST failed with status {e.code} (attempt {attempt + 1}/{max_retries}); "
                         f"retrying in {wait}s"
                     )
                     time.sleep(wait)
             except (URLError, OSError) as e:
+                HTTPClient.log_duration("POST", url, None, started, request_id)
                 last_error = e
                 if attempt < max_retries - 1:
-                    wait = 2 ** attempt
+                    wait = retry_delay if retry_delay is not None else 2 ** attempt
                     logger.debug(
                         f"HTTP POST failed (attempt {attempt + 1}/{max_retries}): {e}; "
                         f"retrying in {wait}s"
@@ -433,26 +1276,111 @@ class HTTPClient:
                 logger.error(f"Unexpected error in HTTP POST: {e}")
                 return None
         
-        logger.debug(f"HTTP POST failed after {max_retries} attempts: {last_error}")
+        if last_error is not None:
+            logger.warning(
+                f"HTTP POST {urlparse(url).path} failed after {attempt + 1} attempt(s): "
+                f"{describe_http_error(url, last_error)}"
+                + (f" request_id={request_id}" if request_id else "")
+            )
         return None
     
+    @staticmethod
+    def auth_headers(token: Optional[str], basic_auth: Optional[Tuple[str, str]]) -> Dict[str, str]:
+        """Credential headers for a relay request.
+
+        Normally the token is the Bearer Authorization. With Basic credentials
+        for an auth proxy in front of the relay, Basic takes Authorization (the
+        header nginx auth_basic, oauth2-proxy and the like check; not
+        Proxy-Authorization, which urllib hands to any forward proxy instead)
+        and the token goes in X-Reach-Token.
+        """
+        if basic_auth:
+            headers = {"Authorization": HTTPClient.basic_auth_header(*basic_auth)}
+            if token:
+                headers["X-Reach-Token"] = token
+            return headers
+        return {"Authorization": f"Bearer {token}"} if token else {}
+
+    @staticmethod
+    def basic_auth_header(user: str, password: str) -> str:
+        """HTTP Basic credentials as a header value."""
+        credentials = base64.b64encode(f"{user}:{password}".encode("utf-8"))
+        return f"Basic {credentials.decode('ascii')}"
+
+    @staticmethod
+    def is_basic_challenge(error: HTTPError) -> bool:
+        """True if an HTTP error carries a `WWW-Authenticate: Basic` challenge."""
+        challenge = (error.headers.get("WWW-Authenticate") or "") if error.headers else ""
+        return challenge.strip().lower().startswith("basic")
+
+    @staticmethod
+    def log_duration(
+        method: str,
+        url: str,
+        status: Optional[int],
+        started: float,
+        request_id: Optional[str] = None,
+        echoed_id: Optional[str] = None,
+    ) -> None:
+        """Debug-log one request's wall time and status ("error" if none came back).
+
+        `request_id` is our X-Request-Id; `echoed_id` the X-Request-Id the
+        server sent back, if any (a proxy may substitute its own).
+        """
+        elapsed_ms = (time.monotonic() - started) * 1000
+        ids = f" request_id={request_id}" if request_id else ""
+        if echoed_id:
+            ids += f" server_request_id={echoed_id}"
+        logger.debug(
+            f"HTTP {method} {urlparse(url).path} -> {status or 'error'} in {elapsed_ms:.0f}ms{ids}"
+        )
+
+    @staticmethod
+    def read_body(response) -> bytes:
+        """Read a response body, undoing gzip/deflate Content-Encoding.
+
+        Moonraker itself doesn't compress, but a reverse proxy in front of it
+        may. Brotli isn't in the stdlib, so we never advertise it.
+        """
+        body = response.read()
+        encoding = (response.headers.get("Content-Encoding") or "").strip().lower()
+        if encoding == "gzip":
+            return gzip.decompress(body)
+        if encoding == "deflate":
+            # RFC 9110 deflate is zlib-wrapped, but some servers send raw deflate.
+            try:
+                return zlib.decompress(body)
+            except zlib.error:
+                return zlib.decompress(body, -zlib.MAX_WBITS)
+        return body
+
     @staticmethod
     def get_json(
         url: str,
         timeout: int = 10,
         max_retries: int = 3,
+        retry_delay: Optional[float] = None,
     ) -> Optional[Dict[str, Any]]:
-        """GET JSON data; retry on failure."""
+        """GET JSON data; retry connection errors and 5xx responses.
+
+        Retries back off exponentially unless `retry_delay` gives a fixed
+        spacing. 4xx responses are returned as failures immediately since
+        repeating the same request won't change the answer.
+        """
         last_error = None
         for attempt in range(max_retries):
             try:
-                with urlopen(url, timeout=timeout) as response:
-                    response_body = response.read().decode("utf-8")
+                req = Request(url, headers={"Accept-Encoding": "gzip, deflate"}, method="GET")
+                with HTTPClient.open(req, timeout=timeout) as response:
+                    response_body = HTTPClient.read_body(response).decode("utf-8")
                     return json.loads(response_body)
             except (URLError, OSError) as e:
                 last_error = e
+                if isinstance(e, HTTPError) and 400 <= e.code < 500:
+                    logger.debug(f"HTTP GET received {e.code} (no retry): {e.reason}")
+                    break
                 if attempt < max_retries - 1:
-                    wait = 2 ** attempt
+                    wait = retry_delay if retry_delay is not None else 2 ** attempt
                     logger.debug(
                         f"HTTP GET failed (attempt {attempt + 1}/{max_retries}): {e}; "
                         f"retrying in {wait}s"
@@ -462,126 +1390,672 @@ class HTTPClient:
                 logger.error(f"Unexpected error in HTTP GET: {e}")
                 return None
         
-        logger.debug(f"HTTP GET failed after {max_retries} attempts: {last_error}")
+        if last_error is not None:
+            logger.debug(
+                f"HTTP GET {urlparse(url).path} failed after {attempt + 1} attempt(s): "
+                f"{describe_http_error(url, last_error)}"
+            )
         return None
 
 # ============================================================================
-# Moonraker Client
+# Moonraker response parsing
 # ============================================================================
 
-class MoonrakerClient:
-    """Queries Moonraker API for printer state."""
-    
-    def __init__(self, url: str):
-        self.url = url.rstrip("/")
-    
-    def get_status(self) -> Optional[Dict[str, Any]]:
-        """
-        Query Moonraker for temperatures, job, system health, fans, and motion.
-        Provides rich telemetry for the RTDB live dashboard.
-        """
-        try:
-            # Query printer objects: temperatures (nozzle, bed), job state, cpu/memory,
-            # fan speed, gcode move (feed rate / flow rate factors), toolhead position.
-            query_url = (
-                f"{self.url}/printer/objects/query?"
-                "extruder=temperature,target&"
-                "heater_bed=temperature,target&"
-                "print_stats=filename,total_duration,print_duration,filament_used,state&"
-                "display_status=message&"
-                "system_stats=cputime,memavail,cpu_percent,memory&"
-                "fan=speed&"
-                "gcode_move=speed,speed_factor,extrude_factor&"
-                "toolhead=position&"
-                "virtual_sdcard=progress,is_active,file_position"
-            )
-            
-            response = HTTPClient.get_json(query_url, timeout=5)
-            if not response or "result" not in response:
-                logger.warning("Moonraker query returned invalid response")
-                return None
-            
-            result = response.get("result", {})
-            status = result.get("status", {})
+# Moonraker print_stats.state -> relay job state. The relay's job.state enum
+# is: idle, printing, paused, complete (finished successfully), cancelled
+# (user cancel), error (the print failed) and unknown. "failed" is the
+# history component's spelling.
+_JOB_STATE_MAP = {
+    "standby": "idle",
+    "printing": "printing",
+    "paused": "paused",
+    "complete": "complete",
+    "cancelled": "cancelled",
+    "error": "error",
+    "failed": "error",
+}
 
-            extruder = status.get("extruder", {})
-            heater_bed = status.get("heater_bed", {})
-            
-            # Extract temperatures — include setpoint targets
-            temperatures = {
-                "nozzle": extruder.get("temperature"),
-                "nozzleTarget": extruder.get("target"),
-                "bed": heater_bed.get("temperature"),
-                "bedTarget": heater_bed.get("target"),
-                "chamber": None,  # K1C doesn't typically have a chamber sensor
-            }
+# Raw states already reported as unmapped, so each new one is logged once.
+_UNMAPPED_JOB_STATES: set = set()
 
-            # Extract fan speed (part cooling fan, 0.0–1.0)
-            fan = status.get("fan", {})
-            fans = {
-                "partCooling": fan.get("speed"),
-            }
 
-            # Extract motion/positioning data
-            gcode_move = status.get("gcode_move", {})
-            toolhead = status.get("toolhead", {})
-            position = toolhead.get("position", [None, None, None, None])
-            motion = {
-                "x": position[0] if len(position) > 0 else None,
-                "y": position[1] if len(position) > 1 else None,
-                "z": position[2] if len(position) > 2 else None,
-                "speed": gcode_move.get("speed"),
-                "speedFactor": gcode_move.get("speed_factor"),
-                "extrudeFactor": gcode_move.get("extrude_factor"),
-            }
+def map_job_state(state: str, message: Optional[str] = None) -> str:
+    """Map a Moonraker print_stats state to our enum, warning once per unknown value.
 
-            # Extract job info
-            print_stats = status.get("print_stats", {})
-            virtual_sdcard = status.get("virtual_sdcard", {})
-            job_state = print_stats.get("state", "unknown")
-            
-            # Map Moonraker states to our enum
-            state_map = {
-                "standby": "idle",
-                "printing": "printing",
-                "paused": "paused",
-                "error": "error",
+    A cancel that carries an error message (a macro aborting on a fault)
+    is reported as `error` rather than a user cancel.
+    """
+    mapped = _JOB_STATE_MAP.get(state)
+    if mapped == "cancelled" and (message or "").strip():
+        return "error"
+    if mapped is not None:
+        return mapped
+    if state not in _UNMAPPED_JOB_STATES:
+        _UNMAPPED_JOB_STATES.add(state)
+        logger.warning(f"Unmapped Moonraker job state {state!r}; reporting as 'unknown'")
+    return "unknown"
+
+
+# Relay job states a print ends in -> the telemetry `jobEvent` type sent
+# once when a print leaves printing/paused for that state.
+_JOB_END_EVENTS = {
+    "complete": "print_complete",
+    "cancelled": "print_cancelled",
+    "error": "print_failed",
+}
+
+
+def job_end_event(previous_state: Optional[str], job: Optional[Dict[str, Any]]) -> Optional[Dict[str, Any]]:
+    """The print-finished edge event for a printing/paused -> complete/cancelled/error change.
+
+    None on every other sample, so the relay can alert once per finished
+    print instead of re-deriving the edge from successive job states.
+    """
+    if previous_state not in ("printing", "paused") or not job:
+        return None
+    event_type = _JOB_END_EVENTS.get(job.get("state"))
+    if event_type is None:
+        return None
+    return {"type": event_type, "filename": job.get("filename")}
+
+
+def derive_progress(
+    display_status: Dict[str, Any],
+    virtual_sdcard: Dict[str, Any],
+    print_stats: Dict[str, Any],
+    estimated_time: Optional[float] = None,
+) -> Optional[float]:
+    """Job progress in percent (0–100) from the best available source.
+
+    Precedence: display_status.progress (M73 from the slicer, else Klipper's
+    own file progress), then virtual_sdcard.progress (bytes read) for configs
+    without [display_status], then print_duration over the slicer's
+    `estimated_time` from the file metadata as a last resort. None when
+    none is available.
+    """
+    fraction = None
+    for value in (
+        (display_status or {}).get("progress"),
+        (virtual_sdcard or {}).get("progress"),
+    ):
+        if isinstance(value, (int, float)):
+            fraction = value
+            break
+    if fraction is None:
+        if not isinstance(estimated_time, (int, float)) or estimated_time <= 0:
+            return None
+        fraction = ((print_stats or {}).get("print_duration") or 0) / estimated_time
+    return min(max(fraction * 100.0, 0.0), 100.0)
+
+
+def parse_bed_mesh(status: Dict[str, Any]) -> Optional[Dict[str, Any]]:
+    """Summarise the bed_mesh object; None when [bed_mesh] isn't configured."""
+    bed_mesh = status.get("bed_mesh")
+    if not isinstance(bed_mesh, dict):
+        return None
+    profile = bed_mesh.get("profile_name") or None
+    points = [
+        z
+        for row in bed_mesh.get("probed_matrix") or []
+        for z in row
+        if isinstance(z, (int, float))
+    ]
+    # Peak-to-valley spread of the probed points, in mm.
+    mesh_range = round(max(points) - min(points), 4) if profile and points else None
+    return {
+        "loaded": profile is not None,
+        "profile": profile,
+        "range": mesh_range,
+    }
+
+
+def parse_spool(spool_id: Any, response: Optional[Dict[str, Any]]) -> Dict[str, Any]:
+    """Reduce a Spoolman spool (via Moonraker's proxy) to id, material and grams left."""
+    spool = (response or {}).get("result", response) or {}
+    filament = spool.get("filament") or {}
+    remaining = spool.get("remaining_weight")
+    return {
+        "spoolId": spool_id,
+        "material": filament.get("material"),
+        "remainingWeight": round(remaining, 1) if isinstance(remaining, (int, float)) else None,
+    }
+
+
+def parse_errors(status: Dict[str, Any]) -> list:
+    """Derive telemetry errors from Klippy's host state and the print state."""
+    errors = []
+    webhooks = status.get("webhooks", {})
+    klippy_state = webhooks.get("state")
+    if klippy_state in ("shutdown", "error"):
+        errors.append({
+            "type": f"klippy_{klippy_state}",
+            "severity": "critical",
+            "message": (webhooks.get("state_message") or "").strip() or f"Klippy {klippy_state}",
+        })
+    print_stats = status.get("print_stats", {})
+    if map_job_state(print_stats.get("state", "unknown"), print_stats.get("message")) == "error":
+        errors.append({
+            "type": "print_error",
+            "severity": "warning",
+            "message": print_stats.get("message") or "Print failed",
+        })
+    return errors
+
+
+# Discovered printer objects reported under temperatures.sensors: extra
+# extruders and named sensors/heaters, which vary per printer and config.
+_SENSOR_OBJECT_PATTERN = re.compile(
+    r"^(extruder\d+|(temperature_sensor|temperature_fan|heater_generic) \S.*)$"
+)
+
+
+def select_sensor_objects(objects: Any) -> list:
+    """Pick the sensor/heater objects out of /printer/objects/list."""
+    if not isinstance(objects, list):
+        return []
+    return sorted(o for o in objects if isinstance(o, str) and _SENSOR_OBJECT_PATTERN.match(o))
+
+
+class TemperatureWindow:
+    """Min/max/average of nozzle and bed temperatures since the last send.
+
+    Every Moonraker sample is added; the summary is merged into the next
+    telemetry payload and the window then starts over. With one poll per
+    telemetry cycle the three values simply equal the instantaneous reading.
+    """
+
+    _SENSORS = ("nozzle", "bed")
+
+    def __init__(self):
+        self.samples: Dict[str, list] = {name: [] for name in self._SENSORS}
+
+    def add(self, temperatures: Dict[str, Any]) -> None:
+        for name in self._SENSORS:
+            value = (temperatures or {}).get(name)
+            if isinstance(value, (int, float)):
+                self.samples[name].append(float(value))
+
+    def summary(self) -> Dict[str, Optional[float]]:
+        result: Dict[str, Optional[float]] = {}
+        for name, values in self.samples.items():
+            result[f"{name}Min"] = round(min(values), 2) if values else None
+            result[f"{name}Max"] = round(max(values), 2) if values else None
+            result[f"{name}Avg"] = round(sum(values) / len(values), 2) if values else None
+        return result
+
+    def reset(self) -> None:
+        for values in self.samples.values():
+            values.clear()
+
+
+class SharedCadence:
+    """Phase-aligned timing: heartbeat on every `ratio`th telemetry tick.
+
+    With REACH_LINK_SHARED_CADENCE=N, register and telemetry stay separate
+    requests but go out back to back from the same loop pass, so the relay
+    sees them together; the heartbeat interval (and nextCheckIn) is unused.
+    """
+
+    def __init__(self, ratio: int):
+        self.ratio = ratio
+        self.ticks = 0
+
+    def next_tick(self) -> bool:
+        """Count one telemetry tick; True if the heartbeat is due on it."""
+        due = self.ticks % self.ratio == 0
+        self.ticks += 1
+        return due
+
+
+class TelemetryPacer:
+    """Widens the telemetry interval while cycles take longer than the interval.
+
+    A slow relay or Moonraker can make one snapshot-and-send outlast the
+    configured interval, so every cycle starts late and samples pile up.
+    After a few consecutive slow cycles the effective interval is raised to
+    twice the measured duration (capped at REACH_LINK_MAX_INTERVAL); the first
+    cycle that fits the configured interval again restores it.
+    """
+
+    SLOW_CYCLES = 3
+
+    def __init__(self):
+        self.slow_cycles = 0
+        self.effective: Optional[int] = None
+
+    def interval(self, configured: int) -> int:
+        return max(configured, self.effective or 0)
+
+    def record(self, duration: float, configured: int, max_interval: int) -> None:
+        if duration <= configured:
+            self.slow_cycles = 0
+            if self.effective is not None:
+                self.effective = None
+                logger.info(f"Telemetry caught up; interval back to {configured}s")
+            return
+        self.slow_cycles += 1
+        if self.slow_cycles < self.SLOW_CYCLES:
+            return
+        widened = min(max_interval, max(configured, math.ceil(duration * 2)))
+        if widened > self.interval(configured):
+            self.effective = widened
+            logger.warning(
+                f"Telemetry cycle took {duration:.1f}s, lagging {duration - configured:.1f}s behind "
+                f"the {configured}s interval for {self.slow_cycles} cycles; widening it to {widened}s"
+            )
+
+
+class ErrorDeduplicator:
+    """Suppresses re-sending an identical error more often than once per window.
+
+    Errors are identified by (type, message). A repeat inside the window is
+    dropped; after the window it goes out again flagged `repeated` as a
+    "still failing" reminder. When a condition clears, its state is forgotten
+    so a later recurrence is reported immediately. filter() only selects;
+    an error counts as sent once mark_sent() confirms its delivery, so a
+    failed send doesn't suppress the retry.
+    """
+
+    def __init__(self, window: int):
+        self.window = window
+        self.last_sent: Dict[Tuple[Any, Any], float] = {}
+
+    def filter(self, errors: list, now: float) -> list:
+        active = {(e.get("type"), e.get("message")) for e in errors}
+        self.last_sent = {sig: ts for sig, ts in self.last_sent.items() if sig in active}
+        to_send = []
+        for error in errors:
+            last = self.last_sent.get((error.get("type"), error.get("message")))
+            if last is not None and now - last < self.window:
+                continue
+            if last is not None:
+                error = dict(error, repeated=True)
+            to_send.append(error)
+        return to_send
+
+    def mark_sent(self, errors: list, now: float) -> None:
+        for error in errors:
+            self.last_sent[(error.get("type"), error.get("message"))] = now
+
+
+class ErrorQueue:
+    """Errors waiting for the next telemetry send, coalesced by (type, message).
+
+    Between sends a quick Klippy state probe feeds the queue; a new critical
+    error is flushed immediately by an out-of-band send, while info/warning
+    errors ride the next regular cycle however often they were seen.
+    """
+
+    def __init__(self):
+        self.pending: Dict[Tuple[Any, Any], Dict[str, Any]] = {}
+
+    def push(self, errors: list) -> None:
+        for error in errors:
+            self.pending.setdefault((error.get("type"), error.get("message")), error)
+
+    def drain(self) -> list:
+        errors = list(self.pending.values())
+        self.pending.clear()
+        return errors
+
+
+def collect_system_health(status: Dict[str, Any]) -> Dict[str, Any]:
+    """Host health from Moonraker's system_stats object."""
+    sys_stats = status.get("system_stats", {})
+    return {
+        "cpuPercent": sys_stats.get("cpu_percent"),
+        "memoryPercent": None,  # Would need total_memory to calculate
+        "diskPercent": None,  # Moonraker doesn't expose disk usage via this endpoint
+    }
+
+
+def parse_recent_jobs(response: Dict[str, Any]) -> list:
+    """Reduce a /server/history/list response to the fields the relay shows."""
+    jobs = response.get("result", {}).get("jobs", []) or []
+    return [
+        {
+            "filename": job.get("filename"),
+            "status": job.get("status"),
+            "printDuration": int(job.get("print_duration") or 0),
+            "totalDuration": int(job.get("total_duration") or 0),
+            "endTime": job.get("end_time"),
+        }
+        for job in jobs
+        if isinstance(job, dict)
+    ]
+
+
+def _as_float(value: Any) -> Optional[float]:
+    try:
+        return float(value)
+    except (TypeError, ValueError):
+        return None
+
+
+def parse_printer_limits(status: Dict[str, Any]) -> Dict[str, Any]:
+    """Kinematics and velocity/accel limits from the configfile/toolhead objects.
+
+    Prefers the parsed `configfile.settings.printer` section; older Klipper
+    only exposes the raw `configfile.config.printer` strings. toolhead's live
+    limits fill in whatever the config doesn't state.
+    """
+    configfile = status.get("configfile", {}) or {}
+    printer = (
+        (configfile.get("settings") or {}).get("printer")
+        or (configfile.get("config") or {}).get("printer")
+        or {}
+    )
+    toolhead = status.get("toolhead", {}) or {}
+    return {
+        "kinematics": (str(printer.get("kinematics") or "").strip() or None),
+        "maxVelocity": _as_float(printer.get("max_velocity", toolhead.get("max_velocity"))),
+        "maxAccel": _as_float(printer.get("max_accel", toolhead.get("max_accel"))),
+    }
+
+
+def parse_machine_hint(response: Dict[str, Any]) -> Dict[str, Optional[str]]:
+    """Best-effort make/model hint from /machine/system_info (distribution and board)."""
+    system_info = response.get("result", {}).get("system_info", {}) or {}
+    cpu_info = system_info.get("cpu_info", {}) or {}
+    distribution = system_info.get("distribution", {}) or {}
+    return {
+        "make": (distribution.get("name") or "").strip() or None,
+        "model": (cpu_info.get("model") or "").strip() or None,
+    }
+
+
+def parse_job_totals(response: Dict[str, Any]) -> Dict[str, Any]:
+    """Reduce a /server/history/totals response to the lifetime `stats` fields."""
+    totals = response.get("result", {}).get("job_totals", {}) or {}
+    return {
+        "totalJobs": int(totals.get("total_jobs") or 0),
+        "totalPrintTime": int(totals.get("total_print_time") or 0),
+        "totalFilamentUsed": totals.get("total_filament_used"),
+        "longestJob": int(totals.get("longest_job") or 0),
+    }
+
+
+def parse_job_queue(response: Dict[str, Any]) -> Dict[str, Any]:
+    """Reduce a /server/job_queue/status response to queue state and pending files."""
+    result = response.get("result", {}) or {}
+    jobs = result.get("queued_jobs", []) or []
+    return {
+        "state": result.get("queue_state"),
+        "jobs": [
+            {
+                "filename": job.get("filename"),
+                "jobId": job.get("job_id"),
+                "timeAdded": job.get("time_added"),
+            }
+            for job in jobs
+            if isinstance(job, dict)
+        ],
+    }
+
+class EtaEstimator:
+    """Chooses between remaining-time estimates derived from different progress bases.
+
+    Each basis gives remaining = elapsed / fraction - elapsed. Between two
+    samples a good estimate should fall by about the wall time that passed,
+    so the basis whose ETA deviated least from that expectation is reported.
+    """
+
+    def __init__(self):
+        self.previous: Dict[str, Tuple[float, int]] = {}
+
+    def update(
+        self, now: float, print_duration: float, fractions: Dict[str, Optional[float]]
+    ) -> Tuple[Optional[int], Optional[str]]:
+        """Return (eta_seconds, method) or (None, None) when no basis is usable yet."""
+        best: Tuple[Optional[int], Optional[str]] = (None, None)
+        best_drift = None
+        current: Dict[str, Tuple[float, int]] = {}
+        for method, fraction in fractions.items():
+            if not isinstance(fraction, (int, float)) or fraction <= 0.01 or print_duration <= 0:
+                continue
+            eta = int(max(0, print_duration / min(fraction, 1.0) - print_duration))
+            current[method] = (now, eta)
+            previous = self.previous.get(method)
+            # No history yet counts as perfectly stable, so the first listed
+            # basis wins until there is something to compare.
+            drift = abs(eta - (previous[1] - (now - previous[0]))) if previous else 0
+            if best_drift is None or drift < best_drift:
+                best, best_drift = (eta, method), drift
+        self.previous = current
+        return best
+
+# ============================================================================
+# Slow-refresh cache
+# ============================================================================
+
+class PeriodicCache:
+    """Holds a slow-changing value and re-fetches it at most once per interval.
+
+    Used for telemetry fields (print history, etc.) that are too expensive or
+    too static to query on every telemetry cycle. An interval of 0 disables
+    the fetch entirely and get() always returns None.
+    """
+
+    def __init__(self, name: str, interval: int, fetch):
+        self.name = name
+        self.interval = interval
+        self.fetch = fetch
+        self.value: Any = None
+        self.fetched_at = 0.0
+
+    def get(self, now: float) -> Any:
+        """Return the cached value, refreshing it first if it has gone stale."""
+        if self.interval <= 0:
+            return None
+        if self.fetched_at and now - self.fetched_at < self.interval:
+            return self.value
+        self.fetched_at = now
+        try:
+            self.value = self.fetch()
+        except Exception as e:
+            logger.debug(f"Failed to refresh {self.name}: {e}")
+            self.value = None
+        return self.value
+
+# ============================================================================
+# Moonraker Client
+# ============================================================================
+
+class MoonrakerClient:
+    """Queries Moonraker API for printer state."""
+    
+    def __init__(self, url: str, extra_objects: Optional[list] = None):
+        self.url = url.rstrip("/")
+        # User-chosen objects queried in full and reported verbatim as `extra`
+        self.extra_objects = extra_objects or []
+        self._health_executor: Optional[ThreadPoolExecutor] = None
+        self._health_future = None
+        self.eta = EtaEstimator()
+        # Sensor objects from the last discovery; None until (re)discovered
+        self.sensor_objects: Optional[list] = None
+        # Static kinematics/limits for registration; None until fetched
+        self.printer_limits: Optional[Dict[str, Any]] = None
+        self._klippy_state: Optional[str] = None
+        # (filename, slicer estimated_time) from the last file metadata lookup
+        self._slicer_estimate: Tuple[Optional[str], Optional[float]] = (None, None)
+
+    def discover_objects(self) -> Optional[list]:
+        """
+        Fetch /printer/objects/list and return the sensor objects to query.
+        Returns None when Klippy isn't ready or Moonraker is unreachable, so
+        discovery is retried on the next cycle.
+        """
+        response = HTTPClient.get_json(f"{self.url}/printer/objects/list", timeout=5, max_retries=1)
+        if not response or "result" not in response:
+            logger.debug("Moonraker object list unavailable")
+            return None
+        sensors = select_sensor_objects(response["result"].get("objects"))
+        logger.info(f"Discovered {len(sensors)} sensor object(s): {', '.join(sensors) or 'none'}")
+        return sensors
+
+    def build_status_query(self) -> str:
+        """Object query URL for get_status, including discovered and extra objects."""
+        # Query printer objects: temperatures (nozzle, bed), job state, cpu/memory,
+        # fan speed, gcode move (feed rate / flow rate factors), toolhead position.
+        query_url = (
+            f"{self.url}/printer/objects/query?"
+            "extruder=temperature,target,power&"
+            "heater_bed=temperature,target,power&"
+            "print_stats=filename,total_duration,print_duration,filament_used,state,message,info&"
+            "webhooks=state,state_message&"
+            "display_status=message,progress&"
+            "system_stats=cputime,memavail,cpu_percent,memory&"
+            "fan=speed&"
+            "gcode_move=speed,speed_factor,extrude_factor&"
+            "toolhead=position&"
+            "virtual_sdcard=progress,is_active,file_position,file_size&"
+            "bed_mesh=profile_name,probed_matrix"
+        )
+        for name in self.sensor_objects or []:
+            query_url += f"&{quote(name)}=temperature,target"
+        for name in self.extra_objects:
+            query_url += f"&{quote(name)}"
+        return query_url
+
+    def _track_restarts(self, status: Optional[Dict[str, Any]]) -> None:
+        """
+        Drop the discovered objects when Moonraker or Klippy restarts, so the
+        next cycle re-discovers and picks up added or removed hardware.
+        A failed query (Moonraker down) or Klippy returning to "ready" from
+        any other state counts as a restart.
+        """
+        if status is None:
+            self.sensor_objects = None
+            self.printer_limits = None
+            self._klippy_state = None
+            return
+        klippy_state = (status.get("webhooks") or {}).get("state")
+        if klippy_state == "ready" and self._klippy_state not in (None, "ready"):
+            logger.info("Klippy restart detected; re-discovering printer objects")
+            self.sensor_objects = None
+            self.printer_limits = None
+        self._klippy_state = klippy_state
+
+    def get_status(self) -> Optional[Dict[str, Any]]:
+        """
+        Query Moonraker for temperatures, job, system health, fans, and motion.
+        Provides rich telemetry for the RTDB live dashboard.
+        """
+        try:
+            if self.sensor_objects is None:
+                self.sensor_objects = self.discover_objects()
+            sensor_objects = self.sensor_objects or []
+            query_url = self.build_status_query()
+
+            # A couple of quick retries ride out a transient Moonraker hiccup
+            # without turning it into a whole cycle of missing telemetry.
+            response = HTTPClient.get_json(query_url, timeout=5, max_retries=3, retry_delay=0.25)
+            if not response or "result" not in response:
+                logger.warning("Moonraker query returned invalid response")
+                self._track_restarts(None)
+                return None
+            
+            result = response.get("result", {})
+            status = result.get("status", {})
+            self._track_restarts(status)
+
+            extruder = status.get("extruder", {})
+            heater_bed = status.get("heater_bed", {})
+            
+            # Extract temperatures — include setpoint targets and heater PWM
+            # duty (0.0–1.0), which exposes a heater pegged at full power
+            temperatures = {
+                "nozzle": extruder.get("temperature"),
+                "nozzleTarget": extruder.get("target"),
+                "nozzlePower": extruder.get("power"),
+                "bed": heater_bed.get("temperature"),
+                "bedTarget": heater_bed.get("target"),
+                "bedPower": heater_bed.get("power"),
+                "chamber": None,  # K1C doesn't typically have a chamber sensor
+                "sensors": {
+                    name: {
+                        "temperature": (status.get(name) or {}).get("temperature"),
+                        "target": (status.get(name) or {}).get("target"),
+                    }
+                    for name in sensor_objects
+                } or None,
             }
-            job_state = state_map.get(job_state, "unknown")
+
+            # Extract fan speed (part cooling fan, 0.0–1.0)
+            fan = status.get("fan", {})
+            fans = {
+                "partCooling": fan.get("speed"),
+            }
+
+            # Extract motion/positioning data
+            gcode_move = status.get("gcode_move", {})
+            toolhead = status.get("toolhead", {})
+            position = toolhead.get("position", [None, None, None, None])
+            motion = {
+                "x": position[0] if len(position) > 0 else None,
+                "y": position[1] if len(position) > 1 else None,
+                "z": position[2] if len(position) > 2 else None,
+                "speed": gcode_move.get("speed"),
+                "speedFactor": gcode_move.get("speed_factor"),
+                "extrudeFactor": gcode_move.get("extrude_factor"),
+            }
+
+            # Extract job info. No print_stats object at all means there is no
+            # job subsystem (e.g. a resin-style setup), which the relay should
+            # see as a null job rather than one in state "unknown".
+            job = None
+            if "print_stats" in status:
+                print_stats = status["print_stats"] or {}
+                virtual_sdcard = status.get("virtual_sdcard", {})
+                job_state = map_job_state(
+                    print_stats.get("state", "unknown"), print_stats.get("message")
+                )
             
-            total_duration = print_stats.get("total_duration", 0)
-            print_duration = print_stats.get("print_duration", 0)
-            # Use Klipper's file-read-based progress (0.0–1.0) — more accurate
-            # than the wall-clock ratio (print_duration / total_duration).
-            sdcard_progress = virtual_sdcard.get("progress", 0.0) or 0.0
-            progress = sdcard_progress * 100.0
+                total_duration = print_stats.get("total_duration", 0)
+                print_duration = print_stats.get("print_duration", 0)
+                display_status = status.get("display_status", {})
+                progress = derive_progress(display_status, virtual_sdcard, print_stats)
+                if progress is None:
+                    progress = derive_progress(
+                        display_status, virtual_sdcard, print_stats,
+                        self.get_slicer_estimate(print_stats.get("filename")),
+                    )
             
-            # Estimate remaining time from progress fraction and elapsed print time.
-            estimated_time = None
-            if sdcard_progress > 0.01 and print_duration > 0:
-                total_estimated = print_duration / sdcard_progress
-                estimated_time = int(max(0, total_estimated - print_duration))
+                # Estimate remaining time from two progress bases: the slicer's M73
+                # progress (display_status) and bytes read from the file
+                # (virtual_sdcard). The estimator reports whichever is steadier.
+                file_size = virtual_sdcard.get("file_size") or 0
+                estimated_time, eta_method = self.eta.update(
+                    time.time(),
+                    print_duration,
+                    {
+                        "slicer_progress": display_status.get("progress"),
+                        "file_position": (virtual_sdcard.get("file_position") or 0) / file_size
+                        if file_size > 0 else None,
+                    },
+                )
             
-            filament_used = print_stats.get("filament_used")
+                filament_used = print_stats.get("filament_used")
+
+                # Layer counts from SET_PRINT_STATS_INFO; absent with older slicer profiles.
+                layer_info = print_stats.get("info") or {}
             
-            job = {
-                "filename": print_stats.get("filename"),
-                "progress": min(progress, 100.0),
-                "eta": estimated_time,
-                "elapsedTime": int(print_duration),
-                "state": job_state,
-                "totaltime": int(total_duration),
-                "filamentUsed": filament_used,
-                "estimatedTime": estimated_time,
-            }
+                job = {
+                    "filename": print_stats.get("filename"),
+                    "progress": progress,
+                    "eta": estimated_time,
+                    "elapsedTime": int(print_duration),
+                    "state": job_state,
+                    "totaltime": int(total_duration),
+                    "filamentUsed": filament_used,
+                    "estimatedTime": estimated_time,
+                    "etaMethod": eta_method,
+                    "currentLayer": layer_info.get("current_layer"),
+                    "totalLayer": layer_info.get("total_layer"),
+                }
             
             # Extract system health
-            sys_stats = status.get("system_stats", {})
-            system_health = {
-                "cpuPercent": sys_stats.get("cpu_percent"),
-                "memoryPercent": None,  # Would need total_memory to calculate
-                "diskPercent": None,  # Moonraker doesn't expose disk usage via this endpoint
-            }
+            system_health = self.collect_system_health_guarded(status)
             
             return {
                 "temperatures": temperatures,
@@ -589,12 +2063,160 @@ class MoonrakerClient:
                 "motion": motion,
                 "job": job,
                 "system_health": system_health,
+                "bed_mesh": parse_bed_mesh(status),
+                "extra": {name: status.get(name) for name in self.extra_objects} or None,
+                "errors": parse_errors(status),
             }
         
         except Exception as e:
             logger.error(f"Error querying Moonraker: {e}")
             return None
 
+    def get_slicer_estimate(self, filename: Optional[str]) -> Optional[float]:
+        """
+        The slicer's estimated print time (seconds) from the file metadata.
+        Looked up once per filename; None when there is no file or the
+        metadata has no estimate.
+        """
+        if not filename:
+            return None
+        if self._slicer_estimate[0] != filename:
+            response = HTTPClient.get_json(
+                f"{self.url}/server/files/metadata?filename={quote(filename)}", timeout=5, max_retries=1
+            )
+            estimate = ((response or {}).get("result") or {}).get("estimated_time")
+            self._slicer_estimate = (
+                filename, estimate if isinstance(estimate, (int, float)) and estimate > 0 else None
+            )
+        return self._slicer_estimate[1]
+
+    def collect_system_health_guarded(
+        self, status: Dict[str, Any], collector=None
+    ) -> Optional[Dict[str, Any]]:
+        """
+        Run the system health collector on a worker thread, bounded by a timeout.
+        A collector that raises or stalls costs us system_health for this cycle
+        (None) instead of stalling or failing the whole telemetry snapshot.
+        While a stalled run still holds the worker, later cycles skip collection
+        rather than queue more runs behind it.
+        """
+        collector = collector or collect_system_health
+        if self._health_future is not None and not self._health_future.done():
+            logger.debug("System health collection still running; skipping this cycle")
+            return None
+        if self._health_executor is None:
+            self._health_executor = ThreadPoolExecutor(max_workers=1, thread_name_prefix="system-health")
+        try:
+            self._health_future = self._health_executor.submit(collector, status)
+            return self._health_future.result(timeout=_SYSTEM_HEALTH_TIMEOUT_SECS)
+        except FutureTimeoutError:
+            logger.debug(f"System health collection timed out after {_SYSTEM_HEALTH_TIMEOUT_SECS}s")
+        except Exception as e:
+            logger.debug(f"System health collection failed: {e}")
+        return None
+
+    def get_recent_jobs(self, limit: int = 5) -> Optional[list]:
+        """
+        Query Moonraker's job history for the last `limit` prints.
+        Returns None when the [history] component is disabled on the printer
+        (Moonraker answers 404) or the query fails.
+        """
+        response = HTTPClient.get_json(
+            f"{self.url}/server/history/list?limit={limit}&order=desc",
+            timeout=5,
+            max_retries=1,
+        )
+        if not response or "result" not in response:
+            logger.debug("Moonraker job history unavailable")
+            return None
+        return parse_recent_jobs(response)
+
+    def get_printer_limits(self) -> Optional[Dict[str, Any]]:
+        """Kinematics and limits, queried once and again after a restart."""
+        if self.printer_limits is None:
+            response = HTTPClient.get_json(
+                f"{self.url}/printer/objects/query?configfile=settings,config&toolhead=max_velocity,max_accel",
+                timeout=5,
+                max_retries=1,
+            )
+            if not response or "result" not in response:
+                logger.debug("Moonraker printer config unavailable")
+                return None
+            self.printer_limits = parse_printer_limits(response["result"].get("status", {}))
+        return self.printer_limits
+
+    def get_errors(self) -> Optional[list]:
+        """Cheap between-cycles probe of Klippy and print state, reduced to errors."""
+        response = HTTPClient.get_json(
+            f"{self.url}/printer/objects/query?webhooks=state,state_message&print_stats=state,message",
+            timeout=5,
+            max_retries=1,
+        )
+        if not response or "result" not in response:
+            return None
+        return parse_errors(response["result"].get("status", {}))
+
+    def get_machine_hint(self) -> Optional[Dict[str, Optional[str]]]:
+        """Make/model hint from Moonraker's system info; None if the query fails."""
+        response = HTTPClient.get_json(f"{self.url}/machine/system_info", timeout=5, max_retries=1)
+        if not response or "result" not in response:
+            logger.debug("Moonraker system info unavailable")
+            return None
+        return parse_machine_hint(response)
+
+    def get_job_totals(self) -> Optional[Dict[str, Any]]:
+        """
+        Query Moonraker's lifetime job totals.
+        Returns None when the [history] component is disabled or the query fails.
+        """
+        response = HTTPClient.get_json(f"{self.url}/server/history/totals", timeout=5, max_retries=1)
+        if not response or "result" not in response:
+            logger.debug("Moonraker job totals unavailable")
+            return None
+        return parse_job_totals(response)
+
+    def get_job_queue(self) -> Optional[Dict[str, Any]]:
+        """
+        Query Moonraker's job queue for its state and pending jobs.
+        Returns None when [job_queue] isn't enabled (Moonraker answers 404)
+        or the query fails.
+        """
+        response = HTTPClient.get_json(f"{self.url}/server/job_queue/status", timeout=5, max_retries=1)
+        if not response or "result" not in response:
+            logger.debug("Moonraker job queue unavailable")
+            return None
+        return parse_job_queue(response)
+
+    def get_active_filament(self) -> Optional[Dict[str, Any]]:
+        """
+        Look up the active Spoolman spool through Moonraker's spoolman proxy.
+        Returns None when Spoolman isn't configured or no spool is active.
+        """
+        response = HTTPClient.get_json(
+            f"{self.url}/server/spoolman/spool_id", timeout=5, max_retries=1
+        )
+        if not response or "result" not in response:
+            logger.debug("Spoolman not available via Moonraker")
+            return None
+        spool_id = response["result"].get("spool_id")
+        if spool_id is None:
+            return None
+
+        spool = HTTPClient.post_json(
+            f"{self.url}/server/spoolman/proxy",
+            {"request_method": "GET", "path": f"/v1/spool/{spool_id}"},
+            timeout=5,
+            max_retries=1,
+        )
+        return parse_spool(spool_id, spool)
+
+    def execute_gcode(self, script: str, timeout: int = 10) -> bool:
+        """Run a G-code script through /printer/gcode/script; True once Klipper has run it."""
+        response = HTTPClient.post_json(
+            f"{self.url}/printer/gcode/script", {"script": script}, timeout=timeout, max_retries=1
+        )
+        return bool(response and response.get("result") == "ok")
+
     def get_webcam_snapshot(self) -> Optional[bytes]:
         """
         Fetch a JPEG snapshot from the local webcam.
@@ -604,7 +2226,7 @@ class MoonrakerClient:
         try:
             snapshot_url = f"{self.url}/webcam/?action=snapshot"
             req = Request(snapshot_url, method="GET")
-            with urlopen(req, timeout=10) as response:
+            with HTTPClient.open(req, timeout=10) as response:
                 content_type = response.headers.get("Content-Type", "")
                 if "image" not in content_type and "octet" not in content_type:
                     logger.debug(f"Webcam snapshot unexpected content type: {content_type}")
@@ -622,18 +2244,123 @@ class MoonrakerClient:
 # Reach3D Relay Client
 # ============================================================================
 
+def format_timestamp(ts: float, fmt: str) -> Any:
+    """Serialize a payload timestamp: unix milliseconds, or an RFC 3339 UTC string."""
+    if fmt == "rfc3339":
+        return datetime.fromtimestamp(ts, timezone.utc).isoformat(timespec="milliseconds").replace("+00:00", "Z")
+    return int(ts * 1000)
+
+
+_agent_commit: Optional[str] = None
+
+
+def agent_commit() -> str:
+    """Short commit hash of the running agent, or "unknown".
+
+    Prefers the hash stamped in at release time, then `git rev-parse` when
+    running from a checkout that tracks this script. An installed copy that
+    happens to sit inside some other git repository (a config checkout, say)
+    reports "unknown" rather than that repository's HEAD. Resolved once per
+    process.
+    """
+    global _agent_commit
+    if _agent_commit is None:
+        _agent_commit = BUILD_COMMIT
+        if _agent_commit == "unknown":
+            script = os.path.abspath(__file__)
+            git = {
+                "cwd": os.path.dirname(script),
+                "capture_output": True,
+                "text": True,
+                "timeout": 5,
+            }
+            try:
+                tracked = subprocess.run(
+                    ["git", "ls-files", "--error-unmatch", os.path.basename(script)], **git
+                )
+                if tracked.returncode == 0:
+                    result = subprocess.run(["git", "rev-parse", "--short=12", "HEAD"], **git)
+                    if result.returncode == 0 and result.stdout.strip():
+                        _agent_commit = result.stdout.strip()
+            except (OSError, subprocess.SubprocessError):
+                pass
+    return _agent_commit
+
+
+def format_since_last_success(last_ok: Optional[float], now: float) -> str:
+    """`since_last_success_secs=<n>` log field ("never" before any success)."""
+    if last_ok is None:
+        return "since_last_success_secs=never"
+    return f"since_last_success_secs={max(0, int(now - last_ok))}"
+
+
 class RelayClient:
     """Posts heartbeats and telemetry to Reach3D relay server."""
     
-    def __init__(self, relay_url: str, token: str, printer_id: str):
+    def __init__(self, relay_url: str, token: str, printer_id: str, config: Config):
         self.relay_url = relay_url.rstrip("/")
         self.token = token
         self.printer_id = printer_id
+        self.config = config
+        # Telemetry sequence number; +1 per payload for the life of the process
+        # so the relay can spot dropped or reordered samples.
+        self.telemetry_seq = itertools.count(1)
+        # Body of the last successful telemetry POST (printed by --once)
+        self.last_telemetry_response: Optional[Dict[str, Any]] = None
+
+    def _post(
+        self,
+        url: str,
+        payload: Dict[str, Any],
+        timeout: int = 10,
+        quick_retries: bool = False,
+        request_id: Optional[str] = None,
+        max_retries: Optional[int] = None,
+    ) -> Optional[Dict[str, Any]]:
+        """POST to the relay with the configured static headers and auth-proxy credentials.
+
+        `quick_retries` retries 5xx/connection failures REACH_LINK_RELAY_RETRIES
+        times with a short fixed delay, to ride out a load balancer blip
+        within the same cycle; `max_retries` sets the attempt count outright.
+        """
+        retry_kwargs: Dict[str, Any] = {}
+        if max_retries is not None:
+            retry_kwargs = {"max_retries": max_retries}
+        elif quick_retries:
+            retry_kwargs = {
+                "max_retries": self.config.relay_retries + 1,
+                "retry_delay": _RELAY_RETRY_DELAY_SECS,
+            }
+        return HTTPClient.post_json(
+            url,
+            payload,
+            self.token,
+            timeout=timeout,
+            basic_auth=self.config.relay_basic_auth,
+            extra_headers=self.config.relay_headers,
+            hmac_secret=self.config.hmac_secret or None,
+            request_id=request_id,
+            **retry_kwargs,
+        )
     
-    def register_heartbeat(self, uptime_secs: int, version: str = "1.0.0") -> Optional[Dict[str, Any]]:
+    def auth_headers(self) -> Dict[str, str]:
+        """Static headers, Bearer token and auth-proxy credentials for requests not sent via _post."""
+        headers = dict(self.config.relay_headers)
+        headers.update(HTTPClient.auth_headers(self.token, self.config.relay_basic_auth))
+        return headers
+
+    def register_heartbeat(
+        self,
+        uptime_secs: int,
+        version: str = "1.0.0",
+        last_ok: Optional[float] = None,
+        printer_limits: Optional[Dict[str, Any]] = None,
+    ) -> Optional[Dict[str, Any]]:
         """
         POST heartbeat to /api/reach-link/register.
-        Returns response payload if successful.
+        Returns response payload if successful. `last_ok` (the last successful
+        heartbeat) only feeds the failure warning; `printer_limits` adds
+        kinematics/maxVelocity/maxAccel when known.
         """
         url = urljoin(self.relay_url, "/api/reach-link/register")
         # Always report current LAN IP so the platform stays in sync when DHCP reassigns
@@ -641,109 +2368,576 @@ class RelayClient:
         payload = {
             "printerId": self.printer_id,
             "token": self.token,
-            "timestamp": int(time.time() * 1000),
+            "timestamp": format_timestamp(time.time(), self.config.timestamp_format),
             "uptime": uptime_secs,
             "version": version,
+            "commit": agent_commit(),
+            "tags": self.config.tags,
+            "make": self.config.printer_make or None,
+            "model": self.config.printer_model or None,
+            **(printer_limits or {}),
             "printerIPAddress": current_ip,
         }
         
-        response = HTTPClient.post_json(url, payload, self.token, timeout=10)
+        request_id = str(uuid.uuid4())
+        response = self._post(
+            url, payload, timeout=10, quick_retries=True, request_id=request_id
+        )
         if response:
-            logger.info(f"Heartbeat registered; next check-in: {response.get('nextCheckIn', '?')}s")
+            logger.info(
+                f"Heartbeat registered; next check-in: {response.get('nextCheckIn', '?')}s "
+                f"request_id={request_id}"
+            )
             return response
+        logger.warning(
+            f"Heartbeat failed; {format_since_last_success(last_ok, time.time())} "
+            f"request_id={request_id}"
+        )
         return None
     
-    def send_telemetry(self, moonraker_status: Dict[str, Any]) -> bool:
-        """
-        POST telemetry to /api/reach-link/printer-data.
-        Returns True if successful.
-        """
-        url = urljoin(self.relay_url, "/api/reach-link/printer-data")
-        payload = {
+    def build_telemetry_payload(self, moonraker_status: Dict[str, Any]) -> Dict[str, Any]:
+        """Telemetry body shared by the HTTP POST and WebSocket stream paths."""
+        return {
             "printerId": self.printer_id,
             "token": self.token,
-            "timestamp": int(time.time() * 1000),
+            "timestamp": format_timestamp(time.time(), self.config.timestamp_format),
+            "seq": next(self.telemetry_seq),
             "temperatures": moonraker_status.get("temperatures"),
             "fans": moonraker_status.get("fans"),
             "motion": moonraker_status.get("motion"),
             "job": moonraker_status.get("job"),
             "systemHealth": moonraker_status.get("system_health"),
-            "errors": [],
-            "logTail": [],
+            "recentJobs": moonraker_status.get("recent_jobs"),
+            "stats": moonraker_status.get("stats"),
+            "jobQueue": moonraker_status.get("job_queue"),
+            "bedMesh": moonraker_status.get("bed_mesh"),
+            "filament": moonraker_status.get("filament"),
+            "extra": moonraker_status.get("extra"),
+            "errors": moonraker_status.get("errors") or [],
+            "maintenance": bool(moonraker_status.get("maintenance")),
+            "jobEvent": moonraker_status.get("job_event"),
+            "logTail": moonraker_status.get("log_tail") or [],
         }
+
+    def send_telemetry(self, payload: Any, last_ok: Optional[float] = None) -> bool:
+        """
+        POST a telemetry payload (see build_telemetry_payload), or a list of
+        them when batching, to /api/reach-link/printer-data.
+        Returns True if successful. `last_ok` (the last successful send) only
+        feeds the failure warning.
+        """
+        url = urljoin(self.relay_url, "/api/reach-link/printer-data")
         
-        response = HTTPClient.post_json(url, payload, self.token, timeout=10)
+        request_id = str(uuid.uuid4())
+        response = self._post(
+            url, payload, timeout=10, quick_retries=True, request_id=request_id
+        )
         if response:
-            logger.debug("Telemetry sent successfully")
+            logger.debug(f"Telemetry sent successfully request_id={request_id}")
+            self.last_telemetry_response = response
             return True
+        logger.warning(
+            f"Telemetry send failed; {format_since_last_success(last_ok, time.time())} "
+            f"request_id={request_id}"
+        )
+        return False
+
+    def send_webcam_snapshot(self, jpeg_data: bytes) -> bool:
+        """
+        POST webcam JPEG snapshot to /api/reach-link/webcam-snapshot.
+        No retries — if one frame fails, the next capture will succeed.
+        Signed over the raw JPEG bytes when REACH_LINK_HMAC_SECRET is set.
+        """
+        url = urljoin(self.relay_url, "/api/reach-link/webcam-snapshot")
+        headers = self.auth_headers()
+        headers.update({
+            "Content-Type": "image/jpeg",
+            "X-Printer-Id": self.printer_id,
+        })
+        if self.config.hmac_secret:
+            headers.update(signature_headers(self.config.hmac_secret, jpeg_data))
+        try:
+            req = Request(url, data=jpeg_data, headers=headers, method="POST")
+            with HTTPClient.open(req, timeout=15) as response:
+                logger.debug("Webcam snapshot uploaded successfully")
+                return True
+        except HTTPError as e:
+            logger.debug(f"Webcam snapshot upload failed (HTTP {e.code}): {e.reason}")
+        except (URLError, OSError) as e:
+            logger.debug(f"Webcam snapshot upload failed: {e}")
+        except Exception as e:
+            logger.error(f"Unexpected error uploading webcam snapshot: {e}")
         return False
 
-    def send_webcam_snapshot(self, jpeg_data: bytes) -> bool:
-        """
-        POST webcam JPEG snapshot to /api/reach-link/webcam-snapshot.
-        No retries — if one frame fails, the next capture will succeed.
+    def send_disconnect(self, reason: "ExitReason", detail: str = "") -> None:
+        """
+        Best-effort POST to /api/reach-link/disconnect saying why the agent is
+        exiting. One attempt with a short timeout; failures are only logged.
+        """
+        url = urljoin(self.relay_url, "/api/reach-link/disconnect")
+        payload = {
+            "printerId": self.printer_id,
+            "timestamp": format_timestamp(time.time(), self.config.timestamp_format),
+            "reason": reason.name.lower(),
+            "code": reason.code,
+            "detail": detail or None,
+        }
+        try:
+            self._post(url, payload, timeout=_EXIT_NOTICE_TIMEOUT_SECS, max_retries=1)
+        except ValueError:
+            pass  # TOKEN_REVOKED: nobody left to tell
+
+    def pull_command(self) -> Optional[Dict[str, Any]]:
+        """
+        Poll relay for next queued command for this printer.
+        The server holds the connection for up to 25 s (long-poll), so we
+        allow a 30 s socket timeout to avoid premature disconnects.
+        Returns command payload or None when queue is empty.
+        """
+        url = urljoin(self.relay_url, "/api/reach-link/commands/pull")
+        payload = {
+            "printerId": self.printer_id,
+        }
+
+        response = self._post(url, payload, timeout=30)
+        if not response:
+            return None
+
+        return response.get("command")
+
+    def push_command_result(
+        self,
+        request_id: str,
+        status: str,
+        result: Optional[Dict[str, Any]] = None,
+        error: Optional[str] = None,
+    ) -> bool:
+        """
+        Push command execution result back to relay.
+        status must be "completed" or "failed".
+        """
+        url = urljoin(self.relay_url, "/api/reach-link/commands/push")
+        payload: Dict[str, Any] = {
+            "printerId": self.printer_id,
+            "requestId": request_id,
+            "status": status,
+        }
+        if result is not None:
+            payload["result"] = result
+        if error:
+            payload["error"] = error
+
+        response = self._post(url, payload, timeout=10)
+        return response is not None
+
+# ============================================================================
+# Relay WebSocket Stream
+# ============================================================================
+
+class RelayStream:
+    """Minimal RFC 6455 client streaming telemetry frames to the relay.
+
+    Stdlib-only like the rest of the agent. We send masked text frames and
+    read unfragmented text/ping/close frames back; the relay pushes interval
+    updates and commands as JSON text messages. Any failure drops the socket
+    and the caller falls back to HTTP POST until the reconnect backoff expires.
+    """
+
+    _ACCEPT_GUID = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11"
+
+    def __init__(self, relay: RelayClient):
+        self.relay = relay
+        self.sock: Optional[socket.socket] = None
+        self.buffer = b""
+        self.backoff = 1.0
+        self.next_attempt = 0.0
+
+    def send(self, payload: Dict[str, Any], now: float) -> bool:
+        """Send one JSON message, connecting first if needed. False means use HTTP."""
+        if not self.sock:
+            if now < self.next_attempt:
+                return False
+            try:
+                self._connect()
+            except Exception as e:
+                self._drop(now, f"connect failed: {e}")
+                return False
+            self.backoff = 1.0
+            logger.info("Relay WebSocket stream connected")
+        try:
+            self._send_frame(0x1, json.dumps(payload).encode("utf-8"))
+            return True
+        except Exception as e:
+            self._drop(now, f"send failed: {e}")
+            return False
+
+    def poll(self, now: float) -> list:
+        """Return any JSON messages the relay has pushed, without blocking."""
+        messages: list = []
+        if not self.sock:
+            return messages
+        try:
+            while self._readable():
+                chunk = self.sock.recv(65536)
+                if not chunk:
+                    raise ConnectionError("closed by relay")
+                self.buffer += chunk
+            while True:
+                frame = self._next_frame()
+                if frame is None:
+                    break
+                opcode, data = frame
+                if opcode == 0x8:
+                    raise ConnectionError("close frame received")
+                if opcode == 0x9:
+                    self._send_frame(0xA, data)
+                elif opcode == 0x1:
+                    try:
+                        messages.append(json.loads(data.decode("utf-8")))
+                    except ValueError:
+                        logger.debug("Ignoring non-JSON relay stream message")
+        except Exception as e:
+            self._drop(now, str(e))
+        return messages
+
+    def close(self) -> None:
+        """Send a close frame (best effort) and release the socket."""
+        if self.sock:
+            try:
+                self._send_frame(0x8, struct.pack("!H", 1000))
+            except Exception:
+                pass
+            self.sock.close()
+            self.sock = None
+
+    def _drop(self, now: float, reason: str) -> None:
+        if self.sock:
+            try:
+                self.sock.close()
+            except Exception:
+                pass
+        self.sock = None
+        self.buffer = b""
+        self.next_attempt = now + self.backoff
+        logger.warning(
+            f"Relay WebSocket stream unavailable ({reason}); "
+            f"using HTTP POST, reconnecting in {self.backoff:.0f}s"
+        )
+        self.backoff = min(self.backoff * 2, _WS_MAX_BACKOFF_SECS)
+
+    def _connect(self) -> None:
+        parsed = urlparse(self.relay.relay_url)
+        secure = parsed.scheme == "https"
+        host = parsed.hostname or ""
+        port = parsed.port or (443 if secure else 80)
+        sock = socket.create_connection((host, port), timeout=10)
+        try:
+            if secure:
+                context = HTTPClient.ssl_context or ssl.create_default_context()
+                sock = context.wrap_socket(sock, server_hostname=host)
+            key = base64.b64encode(os.urandom(16)).decode("ascii")
+            headers = self.relay.auth_headers()
+            headers.update({
+                "Host": parsed.netloc,
+                "Upgrade": "websocket",
+                "Connection": "Upgrade",
+                "Sec-WebSocket-Key": key,
+                "Sec-WebSocket-Version": "13",
+                "User-Agent": HTTPClient.user_agent,
+                "X-Printer-Id": self.relay.printer_id,
+            })
+            request = f"GET {_WS_STREAM_PATH} HTTP/1.1\r\n"
+            request += "".join(f"{k}: {v}\r\n" for k, v in headers.items()) + "\r\n"
+            sock.sendall(request.encode("utf-8"))
+
+            response = b""
+            while b"\r\n\r\n" not in response:
+                chunk = sock.recv(4096)
+                if not chunk or len(response) > 16384:
+                    raise ConnectionError("bad handshake response")
+                response += chunk
+            head, _, rest = response.partition(b"\r\n\r\n")
+            lines = head.decode("latin-1").split("\r\n")
+            if lines[0].split(" ")[1:2] != ["101"]:
+                raise ConnectionError(f"handshake rejected: {lines[0]}")
+            received = {}
+            for line in lines[1:]:
+                name, _, value = line.partition(":")
+                received[name.strip().lower()] = value.strip()
+            expected = base64.b64encode(
+                hashlib.sha1((key + self._ACCEPT_GUID).encode("ascii")).digest()
+            ).decode("ascii")
+            if received.get("sec-websocket-accept") != expected:
+                raise ConnectionError("invalid Sec-WebSocket-Accept")
+        except Exception:
+            sock.close()
+            raise
+        self.sock = sock
+        self.buffer = rest
+
+    def _readable(self) -> bool:
+        # TLS may already hold decrypted bytes that select() can't see.
+        if isinstance(self.sock, ssl.SSLSocket) and self.sock.pending():
+            return True
+        readable, _, _ = select.select([self.sock], [], [], 0)
+        return bool(readable)
+
+    def _send_frame(self, opcode: int, data: bytes) -> None:
+        # Client frames are always FIN + masked (RFC 6455 section 5.3).
+        header = bytearray([0x80 | opcode])
+        length = len(data)
+        if length < 126:
+            header.append(0x80 | length)
+        elif length < 65536:
+            header.append(0x80 | 126)
+            header += struct.pack("!H", length)
+        else:
+            header.append(0x80 | 127)
+            header += struct.pack("!Q", length)
+        mask = os.urandom(4)
+        header += mask
+        masked = bytes(b ^ mask[i % 4] for i, b in enumerate(data))
+        self.sock.sendall(bytes(header) + masked)
+
+    def _next_frame(self) -> Optional[Tuple[int, bytes]]:
+        buf = self.buffer
+        if len(buf) < 2:
+            return None
+        opcode = buf[0] & 0x0F
+        length = buf[1] & 0x7F
+        offset = 2
+        if length == 126:
+            if len(buf) < 4:
+                return None
+            length = struct.unpack("!H", buf[2:4])[0]
+            offset = 4
+        elif length == 127:
+            if len(buf) < 10:
+                return None
+            length = struct.unpack("!Q", buf[2:10])[0]
+            offset = 10
+        mask = None
+        if buf[1] & 0x80:
+            if len(buf) < offset + 4:
+                return None
+            mask = buf[offset:offset + 4]
+            offset += 4
+        if len(buf) < offset + length:
+            return None
+        data = buf[offset:offset + length]
+        if mask:
+            data = bytes(b ^ mask[i % 4] for i, b in enumerate(data))
+        self.buffer = buf[offset + length:]
+        return opcode, data
+
+# ============================================================================
+# Health Server
+# ============================================================================
+
+class _HealthHTTPServer(ThreadingHTTPServer):
+    daemon_threads = True
+
+
+class _HealthHTTPServerV6(_HealthHTTPServer):
+    """IPv6 listener. Binding `::` also accepts IPv4 (as IPv4-mapped
+    addresses) wherever the host allows dual-stack sockets."""
+
+    address_family = socket.AF_INET6
+
+    def server_bind(self):
+        if self.server_address[0] == "::" and hasattr(socket, "IPV6_V6ONLY"):
+            try:
+                self.socket.setsockopt(socket.IPPROTO_IPV6, socket.IPV6_V6ONLY, 0)
+            except OSError:
+                logger.debug("Dual-stack not available; health server is IPv6-only")
+        super().server_bind()
+
+
+def is_loopback_address(host: str) -> bool:
+    """True for 127.0.0.0/8, ::1 and IPv4-mapped loopback (::ffff:127.0.0.1)."""
+    try:
+        address = ipaddress.ip_address(host.split("%", 1)[0])
+    except ValueError:
+        return False
+    mapped = getattr(address, "ipv4_mapped", None)
+    return (mapped or address).is_loopback
+
+
+class HealthServer:
+    """Serves GET /health so orchestrators and local scripts can probe the agent.
+
+    POST /maintenance switches quiet mode: `{"enabled": true, "duration": 1800}`
+    (both optional; an empty body toggles it for REACH_LINK_MAINTENANCE_SECS).
+    POST /log-level `{"level": "debug"}` changes the log level until restart.
+    POSTs are only accepted from loopback clients or with the printer's
+    token as `Authorization: Bearer <token>`, since the server usually binds
+    all interfaces.
+    """
+
+    def __init__(self, agent: "ReachLinkAgent", addr: Tuple[str, int]):
+        self.agent = agent
+        self.addr = addr
+        self.httpd: Optional[ThreadingHTTPServer] = None
+
+    def _make_handler(self):
+        agent = self.agent
+
+        class Handler(BaseHTTPRequestHandler):
+            def do_GET(self):
+                if self.path.split("?", 1)[0] != "/health":
+                    self._send_json(404, {"error": "not found"})
+                    return
+                self._send_json(200, agent.health_status())
+
+            def do_POST(self):
+                routes = {"/maintenance": agent.set_maintenance, "/log-level": set_log_level}
+                route = routes.get(self.path.split("?", 1)[0])
+                if route is None:
+                    self._send_json(404, {"error": "not found"})
+                    return
+                if not self._authorized():
+                    self._send_json(403, {"error": "POST requires a loopback client or the agent token"})
+                    return
+                length = int(self.headers.get("Content-Length") or 0)
+                try:
+                    body = json.loads(self.rfile.read(length).decode("utf-8")) if length else {}
+                except ValueError:
+                    body = None
+                if not isinstance(body, dict):
+                    self._send_json(400, {"error": "body must be a JSON object"})
+                    return
+                try:
+                    self._send_json(200, route(body))
+                except ValueError as e:
+                    self._send_json(400, {"error": str(e)})
+
+            def _authorized(self) -> bool:
+                if is_loopback_address(self.client_address[0]):
+                    return True
+                header = self.headers.get("Authorization") or ""
+                if not header.startswith("Bearer "):
+                    return False
+                presented = header[len("Bearer "):].strip().encode("utf-8")
+                return any(
+                    hmac.compare_digest(presented, token.encode("utf-8"))
+                    for token in agent.control_tokens() if token
+                )
+
+            def _send_json(self, code: int, body: Dict[str, Any]) -> None:
+                data = json.dumps(body).encode("utf-8")
+                self.send_response(code)
+                self.send_header("Content-Type", "application/json")
+                self.send_header("Content-Length", str(len(data)))
+                self.end_headers()
+                self.wfile.write(data)
+
+            def log_message(self, format, *args):
+                logger.debug(f"[health] {self.address_string()} {format % args}")
+
+        return Handler
+
+    def start(self, required: bool = False) -> bool:
+        """Bind and serve in a daemon thread.
+
+        A failed bind raises RuntimeError when `required` (an orchestrator
+        probing a dead port would otherwise see a healthy-looking process);
+        else it is logged and start() returns False.
         """
-        url = urljoin(self.relay_url, "/api/reach-link/webcam-snapshot")
-        headers = {
-            "Content-Type": "image/jpeg",
-            "Authorization": f"Bearer {self.token}",
-            "X-Printer-Id": self.printer_id,
-        }
+        server_class = _HealthHTTPServerV6 if ":" in self.addr[0] else _HealthHTTPServer
         try:
-            req = Request(url, data=jpeg_data, headers=headers, method="POST")
-            with urlopen(req, timeout=15) as response:
-                logger.debug("Webcam snapshot uploaded successfully")
-                return True
-        except HTTPError as e:
-            logger.debug(f"Webcam snapshot upload failed (HTTP {e.code}): {e.reason}")
-        except (URLError, OSError) as e:
-            logger.debug(f"Webcam snapshot upload failed: {e}")
-        except Exception as e:
-            logger.error(f"Unexpected error uploading webcam snapshot: {e}")
-        return False
+            self.httpd = server_class(self.addr, self._make_handler())
+        except OSError as e:
+            if required:
+                raise HealthBindError(
+                    f"Health server could not bind {self._display_addr()}: {e} "
+                    "(set REACH_LINK_HEALTH_OPTIONAL=1 to run without it)"
+                )
+            logger.error(f"Health server could not bind {self._display_addr()}: {e}")
+            return False
+        threading.Thread(target=self.httpd.serve_forever, name="health-server", daemon=True).start()
+        logger.info(f"Health server listening on {self._display_addr()}")
+        return True
 
-    def pull_command(self) -> Optional[Dict[str, Any]]:
-        """
-        Poll relay for next queued command for this printer.
-        The server holds the connection for up to 25 s (long-poll), so we
-        allow a 30 s socket timeout to avoid premature disconnects.
-        Returns command payload or None when queue is empty.
-        """
-        url = urljoin(self.relay_url, "/api/reach-link/commands/pull")
-        payload = {
-            "printerId": self.printer_id,
-        }
+    def _display_addr(self) -> str:
+        host, port = self.addr
+        return f"[{host}]:{port}" if ":" in host else f"{host}:{port}"
 
-        response = HTTPClient.post_json(url, payload, self.token, timeout=30)
-        if not response:
-            return None
+    def stop(self) -> None:
+        if self.httpd:
+            self.httpd.shutdown()
+            self.httpd.server_close()
+            self.httpd = None
 
-        return response.get("command")
+def loop_stall_secs(
+    now: float, last_activity: float, interval: float, multiplier: int
+) -> Optional[float]:
+    """How long the agent loop has made no progress, if that exceeds the watchdog limit.
 
-    def push_command_result(
-        self,
-        request_id: str,
-        status: str,
-        result: Optional[Dict[str, Any]] = None,
-        error: Optional[str] = None,
-    ) -> bool:
-        """
-        Push command execution result back to relay.
-        status must be "completed" or "failed".
-        """
-        url = urljoin(self.relay_url, "/api/reach-link/commands/push")
-        payload: Dict[str, Any] = {
-            "printerId": self.printer_id,
-            "requestId": request_id,
-            "status": status,
-        }
-        if result is not None:
-            payload["result"] = result
-        if error:
-            payload["error"] = error
+    `last_activity` is stamped between the blocking steps of a pass, so it
+    only goes stale for as long as one step takes. The limit is `multiplier`
+    × the larger loop interval, but never below _WATCHDOG_MIN_SECS, which is
+    longer than the slowest step (a command long-poll retried through a relay
+    outage, about 93s). None while healthy.
+    """
+    stalled = now - last_activity
+    if stalled > max(multiplier * interval, _WATCHDOG_MIN_SECS):
+        return stalled
+    return None
 
-        response = HTTPClient.post_json(url, payload, self.token, timeout=10)
-        return response is not None
+
+class ShutdownCoordinator:
+    """One shutdown signal plus ordered cleanup under a single deadline.
+
+    Loops watch the event handed out by subscribe(); background pieces
+    register a cleanup step (flush, close, stop, join) when they start.
+    finish() runs the steps in registration order, each on a daemon thread,
+    and abandons the rest once REACH_LINK_SHUTDOWN_TIMEOUT has passed, so one
+    stuck step can't hang the exit.
+    """
+
+    def __init__(self, deadline_secs: float):
+        self.deadline_secs = deadline_secs
+        self.event = threading.Event()
+        self.steps: list = []
+
+    def subscribe(self) -> threading.Event:
+        return self.event
+
+    def register(self, name: str, step) -> None:
+        self.steps.append((name, step))
+
+    def trigger(self) -> None:
+        self.event.set()
+
+    def finish(self) -> bool:
+        """Run the cleanup steps; False if the deadline cut any of them short."""
+        self.event.set()
+        deadline = time.monotonic() + self.deadline_secs
+        for index, (name, step) in enumerate(self.steps):
+            worker = threading.Thread(
+                target=self._run_step,
+                args=(name, step),
+                # Keep the caller's name so multi-printer logs still say which printer.
+                name=f"{threading.current_thread().name}-shutdown",
+                daemon=True,
+            )
+            worker.start()
+            worker.join(max(0.0, deadline - time.monotonic()))
+            if worker.is_alive():
+                skipped = [n for n, _ in self.steps[index + 1:]]
+                logger.warning(
+                    f"Shutdown deadline of {self.deadline_secs}s passed during {name}; "
+                    f"abandoning it{' and skipping ' + ', '.join(skipped) if skipped else ''}"
+                )
+                return False
+        return True
+
+    @staticmethod
+    def _run_step(name: str, step) -> None:
+        try:
+            step()
+        except Exception as e:
+            logger.warning(f"Shutdown step {name} failed: {e}")
 
 # ============================================================================
 # Main Agent
@@ -755,8 +2949,8 @@ class ReachLinkAgent:
     def __init__(self, config: Config):
         self.config = config
         self._bootstrap_credentials_if_needed()
-        self.moonraker = MoonrakerClient(config.moonraker_url)
-        self.relay = RelayClient(config.relay_url, config.token, config.printer_id)
+        self.moonraker = MoonrakerClient(config.moonraker_url, config.extra_objects)
+        self.relay = RelayClient(config.relay_url, config.token, config.printer_id, config)
         
         # Initialize Firebase RTDB client if configured
         self.firebase = None
@@ -777,13 +2971,48 @@ class ReachLinkAgent:
         else:
             logger.debug("Firebase RTDB not configured (env vars not set)")
         
-        self.shutdown_event = asyncio.Event()
+        self.shutdown = ShutdownCoordinator(config.shutdown_timeout)
+        self.shutdown_event = self.shutdown.subscribe()
         self.start_time = time.time()
+        # 0.0 makes the first loop pass register immediately, before any sleep.
         self.last_heartbeat = 0.0
         self.last_telemetry = 0.0
         self.last_command_poll = 0.0
         self.last_webcam_capture = 0.0
+        # Stamped before and after each blocking step of a loop pass; read by the watchdog
+        self.last_progress = self.start_time
+        self.last_heartbeat_ok: Optional[float] = None
+        self.last_telemetry_ok: Optional[float] = None
+        self.recent_jobs = PeriodicCache(
+            "recent jobs", config.history_interval, self.moonraker.get_recent_jobs
+        )
+        self.filament = PeriodicCache(
+            "filament", config.filament_interval, self.moonraker.get_active_filament
+        )
+        self.job_stats = PeriodicCache(
+            "job stats",
+            # Also served by [history], so it follows that switch.
+            _JOB_STATS_INTERVAL_SECS if config.history_interval > 0 else 0,
+            self.moonraker.get_job_totals,
+        )
+        self.stream = RelayStream(self.relay) if config.relay_ws else None
+        self.telemetry_batch: list = []
+        self.telemetry_batch_started = 0.0
+        self.error_dedup = ErrorDeduplicator(config.error_dedup_secs)
+        self.error_queue = ErrorQueue()
+        self.last_error_poll = 0.0
+        self.temperature_window = TemperatureWindow()
+        self.telemetry_pacer = TelemetryPacer()
+        self.cadence = SharedCadence(config.shared_cadence) if config.shared_cadence else None
+        self.machine_hint_checked = False
         self.token_revoked = False
+        self.reload_requested = False
+        self.maintenance_until: Optional[float] = None
+        self.log_tail_cursor = 0
+        # Job state from the previous snapshot, for the print-finished jobEvent
+        self.last_job_state: Optional[str] = None
+        # Set to this agent's thread when it runs inside an AgentFleet
+        self.log_tail_thread: Optional[str] = None
 
     def _bootstrap_credentials_if_needed(self):
         """Claim pairing session if token is not pre-provisioned."""
@@ -826,14 +3055,199 @@ class ReachLinkAgent:
 
         logger.info(f"Pairing claim successful. Printer registered as {self.config.printer_id}")
     
+    def _detect_make_model(self) -> None:
+        """Fill unset REACH_LINK_PRINTER_MAKE/MODEL from Moonraker, once it answers.
+
+        Explicit settings always win; detection is retried on each
+        registration until Moonraker responds.
+        """
+        if self.machine_hint_checked or (self.config.printer_make and self.config.printer_model):
+            return
+        hint = self.moonraker.get_machine_hint()
+        if hint is None:
+            return
+        self.machine_hint_checked = True
+        self.config.printer_make = self.config.printer_make or hint["make"] or ""
+        self.config.printer_model = self.config.printer_model or hint["model"] or ""
+
+    def _poll_critical_errors(self) -> bool:
+        """Queue the printer's current errors; True if one is critical and new.
+
+        New means neither sent nor already waiting in the queue, so a critical
+        error whose send failed rides the next regular cycle instead of
+        forcing a send on every probe while the relay is down.
+        """
+        errors = self.moonraker.get_errors()
+        if not errors:
+            return False
+        known = set(self.error_dedup.last_sent) | set(self.error_queue.pending)
+        self.error_queue.push(errors)
+        return any(
+            e.get("severity") == "critical" and (e.get("type"), e.get("message")) not in known
+            for e in errors
+        )
+
+    def _apply_rotated_token(self, response: Dict[str, Any]) -> None:
+        """Adopt a token the relay issued in a register response.
+
+        The relay sends it as `newToken` (older relays: `rotatedToken`). It is
+        used for every later request and, when a state file is configured,
+        persisted so it survives a restart even over an exported
+        REACH_LINK_TOKEN (see Config._adopt_rotated_token). The token itself is
+        never logged.
+        """
+        new_token = str(response.get("newToken") or response.get("rotatedToken") or "").strip()
+        if not new_token or new_token == self.config.token:
+            return
+        self.config.token = new_token
+        self.relay.token = new_token
+        self.config.persist_state()
+        persisted = " and persisted it" if self.config.state_file else ""
+        logger.info(f"Relay rotated the reach-link token; switched to the new token{persisted}")
+
+    def health_status(self) -> Dict[str, Any]:
+        """Snapshot of agent liveness for the /health endpoint."""
+        return {
+            "status": "revoked" if self.token_revoked else "ok",
+            "version": AGENT_VERSION,
+            "printerId": self.config.printer_id,
+            "uptime": int(time.time() - self.start_time),
+            "lastHeartbeatOk": int(self.last_heartbeat_ok * 1000) if self.last_heartbeat_ok else None,
+            "lastTelemetryOk": int(self.last_telemetry_ok * 1000) if self.last_telemetry_ok else None,
+            "maintenanceUntil": int(self.maintenance_until * 1000) if self.in_maintenance() else None,
+        }
+
+    def in_maintenance(self, now: Optional[float] = None) -> bool:
+        """Whether quiet mode is on; clears it once its window has passed."""
+        if self.maintenance_until is None:
+            return False
+        if (now or time.time()) < self.maintenance_until:
+            return True
+        self.maintenance_until = None
+        logger.info("Maintenance mode expired")
+        return False
+
+    def control_tokens(self) -> list:
+        """Tokens accepted on health server POSTs from non-loopback clients."""
+        return [self.relay.token]
+
+    def set_maintenance(self, request: Dict[str, Any]) -> Dict[str, Any]:
+        """Enable, disable or (with no "enabled" key) toggle maintenance mode.
+
+        While on, telemetry keeps flowing but carries `maintenance: true` and
+        no errors, so the relay doesn't alert on a printer being worked on.
+        """
+        now = time.time()
+        enabled = request.get("enabled")
+        if enabled is None:
+            enabled = not self.in_maintenance(now)
+        duration = request.get("duration", self.config.maintenance_secs)
+        if not isinstance(duration, (int, float)) or isinstance(duration, bool) or duration <= 0:
+            raise ValueError("duration must be a positive number of seconds")
+        if enabled:
+            self.maintenance_until = now + duration
+            logger.info(f"Maintenance mode on for {int(duration)}s")
+        elif self.maintenance_until is not None:
+            self.maintenance_until = None
+            logger.info("Maintenance mode off")
+        return {
+            "printerId": self.config.printer_id,
+            "maintenance": bool(enabled),
+            "maintenanceUntil": int(self.maintenance_until * 1000) if enabled else None,
+        }
+
+    def _send_telemetry(self, moonraker_status: Dict[str, Any]) -> Optional[bool]:
+        """Send (or batch) one telemetry sample.
+
+        With REACH_LINK_BATCH_SIZE > 1 samples are held until the batch fills
+        or its oldest sample reaches REACH_LINK_BATCH_MAX_AGE, then sent as
+        one array. Returns None while a sample is only buffered, otherwise
+        whether the send succeeded.
+        """
+        temperatures = moonraker_status.get("temperatures")
+        if isinstance(temperatures, dict):
+            self.temperature_window.add(temperatures)
+            moonraker_status["temperatures"] = dict(temperatures, **self.temperature_window.summary())
+        self.temperature_window.reset()
+
+        lines, self.log_tail_cursor = LOG_TAIL.since(self.log_tail_cursor, self.log_tail_thread)
+        moonraker_status["log_tail"] = clamp_log_tail(lines, self.config.log_tail_max_bytes)
+
+        payload = self.relay.build_telemetry_payload(moonraker_status)
+        if self.config.batch_size <= 1:
+            return self._deliver_telemetry(payload, payload["errors"])
+
+        now = time.time()
+        if not self.telemetry_batch:
+            self.telemetry_batch_started = now
+        self.telemetry_batch.append(payload)
+        if (
+            len(self.telemetry_batch) >= self.config.batch_size
+            or now - self.telemetry_batch_started >= self.config.batch_max_age
+            # Critical errors must not wait in a half-full batch.
+            or any(e.get("severity") == "critical" for e in payload["errors"])
+        ):
+            return self._flush_telemetry_batch()
+        return None
+
+    def _flush_telemetry_batch(self) -> bool:
+        """Send any buffered telemetry samples as a single array."""
+        if not self.telemetry_batch:
+            return True
+        batch, self.telemetry_batch = self.telemetry_batch, []
+        logger.debug(f"Sending telemetry batch of {len(batch)} sample(s)")
+        return self._deliver_telemetry(batch, [e for sample in batch for e in sample["errors"]])
+
+    def _deliver_telemetry(self, payload: Any, errors: list) -> bool:
+        """Send over the relay stream when enabled, else (or on failure) POST it.
+
+        `errors` are the errors the payload carries: marked sent for the
+        dedup window on success, queued again for the next send on failure.
+        """
+        if self.stream and self.stream.send(payload, time.time()):
+            ok = True
+        else:
+            ok = self.relay.send_telemetry(payload, last_ok=self.last_telemetry_ok)
+            if ok and self.relay.last_telemetry_response:
+                self._apply_relay_intervals(self.relay.last_telemetry_response)
+        if ok:
+            self.error_dedup.mark_sent(errors, time.time())
+        else:
+            self.error_queue.push(errors)
+        return ok
+
+    def _poll_stream(self) -> None:
+        """Apply interval updates and commands the relay pushed over the stream."""
+        for message in self.stream.poll(time.time()):
+            self._apply_relay_intervals(message)
+            command_data = message.get("command")
+            if isinstance(command_data, dict):
+                self._process_relay_command(command_data)
+
+    def _apply_relay_intervals(self, message: Dict[str, Any]) -> None:
+        """Adopt nextCheckIn / nextDataInterval from a relay response or stream message."""
+        next_check_in = self.config.clamp_interval("nextCheckIn", message.get("nextCheckIn"))
+        if next_check_in:
+            self.config.heartbeat_interval = next_check_in
+        next_data_interval = self.config.clamp_interval(
+            "nextDataInterval", message.get("nextDataInterval")
+        )
+        if next_data_interval:
+            self.config.telemetry_interval = next_data_interval
+
     def setup_signal_handlers(self):
         """Register SIGTERM/SIGINT handlers for graceful shutdown."""
-        def signal_handler(signum, frame):
-            logger.info(f"Received signal {signum}; shutting down...")
-            self.shutdown_event.set()
-        
-        signal.signal(signal.SIGTERM, signal_handler)
-        signal.signal(signal.SIGINT, signal_handler)
+        install_signal_handlers([self])
+
+    def _apply_config_reload(self) -> None:
+        """Pick up new interval settings after SIGHUP."""
+        self.reload_requested = False
+        changed = self.config.reload_intervals()
+        if changed:
+            summary = ", ".join(f"{k}={v}s" for k, v in changed.items())
+            logger.info(f"Config reloaded: {summary}")
+        else:
+            logger.info("Config reloaded: no interval changes (relay URL/token changes need a restart)")
     
     def proxy_command_to_moonraker(self, command: str, params: Dict[str, Any], timeout: int = 10) -> Dict[str, Any]:
         """
@@ -847,7 +3261,7 @@ class ReachLinkAgent:
         Returns: { "result": {...} } or { "error": "..." }
         """
         try:
-            moonraker_base = "http://127.0.0.1:7125"
+            moonraker_base = self.config.moonraker_url
             command_params = dict(params or {})
             method = str(command_params.pop("__method", "POST")).upper()
             query = command_params.pop("__query", {})
@@ -877,7 +3291,7 @@ class ReachLinkAgent:
                     headers={"Content-Type": "application/json"}
                 )
             
-            with urlopen(req, timeout=timeout) as response:
+            with HTTPClient.open(req, timeout=timeout) as response:
                 response_data = json.loads(response.read().decode("utf-8"))
                 logger.debug(f"Moonraker responded to {command}: {response.status}")
                 return response_data
@@ -891,7 +3305,7 @@ class ReachLinkAgent:
                 logger.warning(f"Moonraker endpoint unavailable for {command}: HTTP {e.code}")
             else:
                 logger.error(f"Moonraker proxy error for {command}: HTTP {e.code} {e.reason}")
-            return {"error": str(e), "errorCode": "moonraker_error"}
+            return {"error": str(e), "errorCode": "moonraker_error", "httpStatus": e.code}
         except URLError as e:
             logger.error(f"Moonraker proxy error for {command}: {e}")
             return {"error": str(e), "errorCode": "moonraker_error"}
@@ -980,7 +3394,8 @@ class ReachLinkAgent:
             pass
 
         logger.info(f"{prefix} Uninstall complete. Exiting.")
-        sys.exit(0)
+        # Runs on a threading.Timer thread, where sys.exit would only end the thread.
+        exit_agent(ExitReason.UNINSTALLED, hard=True)
 
     def _handle_system_command(self, command: str) -> Optional[Dict[str, Any]]:
         """Handle system.* agent control commands.
@@ -990,7 +3405,7 @@ class ReachLinkAgent:
         """
         if command == "system.shutdown":
             logger.info("[system.shutdown] Received shutdown command. Stopping agent.")
-            self.shutdown_event.set()
+            self.shutdown.trigger()
             return {"status": "ok", "message": "agent shutting down"}
 
         if command == "system.uninstall":
@@ -1002,6 +3417,123 @@ class ReachLinkAgent:
 
         return None  # Not a system command
 
+    def _handle_power_command(self, params: Dict[str, Any]) -> Dict[str, Any]:
+        """Switch a Moonraker [power] device on/off/toggle.
+
+        Expects params {"device": "<name>", "action": "on"|"off"|"toggle"}.
+        Returns the Moonraker result, or an {"error": ...} dict that the
+        caller acks back as a failed command. The device list is checked
+        first: Moonraker answers an unknown device with a generic 400, and a
+        404 from these endpoints means the [power] component isn't loaded.
+        """
+        device = str((params or {}).get("device", "")).strip()
+        action = str((params or {}).get("action", "")).strip().lower()
+        if not device:
+            return {"error": "power command requires a device", "errorCode": "invalid_params"}
+        if action not in _POWER_ACTIONS:
+            return {
+                "error": f"invalid power action {action!r}; expected one of {', '.join(_POWER_ACTIONS)}",
+                "errorCode": "invalid_params",
+            }
+
+        devices = self.proxy_command_to_moonraker("machine.device_power.devices", {"__method": "GET"})
+        if devices.get("httpStatus") == 404:
+            return self._power_unavailable()
+        if "error" in devices:
+            return devices
+        known = [entry.get("device") for entry in (devices.get("result") or {}).get("devices") or []]
+        if device not in known:
+            logger.warning(f"[power] Unknown device {device!r}; Moonraker has: {', '.join(map(str, known)) or 'none'}")
+            return {"error": f"power device {device!r} not found", "errorCode": "unknown_device"}
+
+        logger.info(f"[power] Setting device {device!r} -> {action}")
+        result = self.proxy_command_to_moonraker(
+            "machine.device_power.device",
+            {"__method": "POST", "__query": {"device": device, "action": action}},
+        )
+        if result.get("httpStatus") == 404:
+            return self._power_unavailable()
+        return result
+
+    @staticmethod
+    def _power_unavailable() -> Dict[str, Any]:
+        return {
+            "error": "Moonraker has no [power] component configured",
+            "errorCode": "power_unavailable",
+        }
+
+    def _handle_query_command(self, params: Dict[str, Any]) -> Dict[str, Any]:
+        """Run a one-off /printer/objects/query and ack the raw result.
+
+        Expects params {"objects": ["<object name>", ...]}; each object is
+        returned in full. At most _QUERY_MAX_OBJECTS objects, and results
+        over _QUERY_MAX_RESPONSE_BYTES are refused rather than truncated.
+        """
+        objects = (params or {}).get("objects")
+        if (
+            not isinstance(objects, list)
+            or not objects
+            or not all(isinstance(name, str) and name.strip() for name in objects)
+        ):
+            return {
+                "error": "query command requires a non-empty list of object names",
+                "errorCode": "invalid_params",
+            }
+        if len(objects) > _QUERY_MAX_OBJECTS:
+            return {
+                "error": f"query command accepts at most {_QUERY_MAX_OBJECTS} objects, got {len(objects)}",
+                "errorCode": "invalid_params",
+            }
+
+        query = "&".join(quote(name.strip()) for name in objects)
+        logger.info(f"[query] Querying Moonraker objects: {', '.join(objects)}")
+        response = HTTPClient.get_json(
+            f"{self.config.moonraker_url}/printer/objects/query?{query}", timeout=10, max_retries=1
+        )
+        if not response or "result" not in response:
+            return {"error": "Moonraker object query failed", "errorCode": "moonraker_error"}
+        size = len(json.dumps(response["result"]).encode("utf-8"))
+        if size > _QUERY_MAX_RESPONSE_BYTES:
+            return {
+                "error": f"query result is {size} bytes; limit is {_QUERY_MAX_RESPONSE_BYTES}",
+                "errorCode": "response_too_large",
+            }
+        return {"result": response["result"]}
+
+    def _handle_emergency_stop(self, request_id: str, params: Dict[str, Any]) -> Dict[str, Any]:
+        """Emergency-stop the printer, but only if the command names this printer.
+
+        A misrouted command must never halt the wrong machine, so the params
+        have to carry a printer_id equal to ours. Acks with the Klippy state
+        Moonraker reports afterwards.
+        """
+        target = str((params or {}).get("printer_id") or (params or {}).get("printerId") or "").strip()
+        if target != self.config.printer_id:
+            logger.warning(
+                f"[emergency_stop] Rejected command {request_id}: printer_id {target!r} "
+                f"does not match this printer ({self.config.printer_id})"
+            )
+            return {"error": "printer_id mismatch; emergency stop not executed", "errorCode": "printer_mismatch"}
+
+        logger.warning(f"[emergency_stop] Executing emergency stop from command {request_id}")
+        result = self.proxy_command_to_moonraker("printer.emergency_stop", {})
+        if "error" in result:
+            return result
+        info = self.proxy_command_to_moonraker("server.info", {"__method": "GET"})
+        return {"result": "ok", "klippyState": (info.get("result") or {}).get("klippy_state")}
+
+    def _execute_command(
+        self, command: str, params: Dict[str, Any], request_id: str = ""
+    ) -> Dict[str, Any]:
+        """Run a relay/RTDB command: agent-level command types first, else proxy to Moonraker."""
+        if command == "power":
+            return self._handle_power_command(params)
+        if command == "emergency_stop":
+            return self._handle_emergency_stop(request_id, params)
+        if command == "query":
+            return self._handle_query_command(params)
+        return self.proxy_command_to_moonraker(command, params)
+
     def process_pending_firebase_commands(self) -> int:
         """
         Poll and process commands from Firebase RTDB
@@ -1047,7 +3579,7 @@ class ReachLinkAgent:
                     )
 
                     # Execute via Moonraker proxy
-                    result = self.proxy_command_to_moonraker(command, params)
+                    result = self._execute_command(command, params, command_id)
 
                     # Write result
                     if "error" in result:
@@ -1085,6 +3617,69 @@ class ReachLinkAgent:
             logger.error(f"Error in Firebase command processing: {e}")
             return 0
     
+    def _process_relay_command(self, command_data: Dict[str, Any]) -> bool:
+        """Execute one relay command and push its result. Returns False if malformed."""
+        request_id = command_data.get("requestId", "")
+        command = command_data.get("command", "")
+        params = command_data.get("params", {})
+
+        if not request_id or not command:
+            logger.warning("Received malformed relay command payload")
+            return False
+
+        logger.info(f"[relay-command] Processing: id={request_id}, command={command}")
+
+        # Handle system control commands before proxying to Moonraker.
+        system_result = self._handle_system_command(command)
+        if system_result is not None:
+            self.relay.push_command_result(
+                request_id=request_id,
+                status="completed",
+                result=system_result,
+            )
+            return True
+
+        # GCode script commands block Moonraker until the script finishes.
+        # Long operations (e.g. G28 homing, bed mesh calibration) can run for
+        # minutes — far beyond the normal proxy timeout.  Fire the request in a
+        # background thread and immediately acknowledge to the relay so the
+        # command loop stays responsive and the dashboard doesn't see a timeout.
+        if command == "printer.gcode.script":
+            import threading
+            def _run_gcode(cmd=command, p=dict(params or {})):
+                bg_result = self.proxy_command_to_moonraker(cmd, p, timeout=600)
+                if "error" in bg_result:
+                    logger.warning(
+                        f"[relay-command] GCode script error: {bg_result.get('error')}"
+                    )
+                else:
+                    logger.info(f"[relay-command] GCode script completed: {p.get('script', '')}")
+            threading.Thread(target=_run_gcode, daemon=True).start()
+            self.relay.push_command_result(
+                request_id=request_id,
+                status="completed",
+                result={"result": "accepted"},
+            )
+            return True
+
+        result = self._execute_command(command, params, request_id)
+
+        if "error" in result:
+            self.relay.push_command_result(
+                request_id=request_id,
+                status="failed",
+                result=result,
+                error=str(result.get("error", "moonraker_error")),
+            )
+        else:
+            self.relay.push_command_result(
+                request_id=request_id,
+                status="completed",
+                result=result,
+            )
+
+        return True
+
     def process_pending_commands(self) -> int:
         """
         Drain the relay command queue: pull and execute commands until the queue
@@ -1097,72 +3692,14 @@ class ReachLinkAgent:
         try:
             while True:
                 command_data = self.relay.pull_command()
+                self._mark_progress()
                 if not command_data:
                     # Queue is empty - done for this cycle.
                     break
 
-                request_id = command_data.get("requestId", "")
-                command = command_data.get("command", "")
-                params = command_data.get("params", {})
-
-                if not request_id or not command:
-                    logger.warning("Received malformed relay command payload")
-                    continue
-
-                logger.info(f"[relay-command] Processing: id={request_id}, command={command}")
-
-                # Handle system control commands before proxying to Moonraker.
-                system_result = self._handle_system_command(command)
-                if system_result is not None:
-                    self.relay.push_command_result(
-                        request_id=request_id,
-                        status="completed",
-                        result=system_result,
-                    )
-                    processed += 1
-                    continue
-
-                # GCode script commands block Moonraker until the script finishes.
-                # Long operations (e.g. G28 homing, bed mesh calibration) can run for
-                # minutes — far beyond the normal proxy timeout.  Fire the request in a
-                # background thread and immediately acknowledge to the relay so the
-                # command loop stays responsive and the dashboard doesn't see a timeout.
-                if command == "printer.gcode.script":
-                    import threading
-                    def _run_gcode(cmd=command, p=dict(params or {})):
-                        bg_result = self.proxy_command_to_moonraker(cmd, p, timeout=600)
-                        if "error" in bg_result:
-                            logger.warning(
-                                f"[relay-command] GCode script error: {bg_result.get('error')}"
-                            )
-                        else:
-                            logger.info(f"[relay-command] GCode script completed: {p.get('script', '')}")
-                    threading.Thread(target=_run_gcode, daemon=True).start()
-                    self.relay.push_command_result(
-                        request_id=request_id,
-                        status="completed",
-                        result={"result": "accepted"},
-                    )
+                if self._process_relay_command(command_data):
                     processed += 1
-                    continue
-
-                result = self.proxy_command_to_moonraker(command, params)
-
-                if "error" in result:
-                    self.relay.push_command_result(
-                        request_id=request_id,
-                        status="failed",
-                        result=result,
-                        error=str(result.get("error", "moonraker_error")),
-                    )
-                else:
-                    self.relay.push_command_result(
-                        request_id=request_id,
-                        status="completed",
-                        result=result,
-                    )
-
-                processed += 1
+                self._mark_progress()
 
             return processed
         except ValueError as e:
@@ -1172,7 +3709,7 @@ class ReachLinkAgent:
                     "Action required: Re-run printer setup to generate a new token and reinstall reach-link agent."
                 )
                 self.token_revoked = True
-                self.shutdown_event.set()
+                self.shutdown.trigger()
                 return processed
             raise
         except Exception as e:
@@ -1204,12 +3741,9 @@ class ReachLinkAgent:
 
             # Step 1 — Check version from platform relay (no auth required)
             version_url = f"{self.config.relay_url.rstrip('/')}/api/reach-link/version"
-            req = Request(
-                version_url,
-                headers={"User-Agent": f"reach-link-agent/{AGENT_VERSION}"},
-            )
+            req = Request(version_url)
             try:
-                with urlopen(req, timeout=10) as resp:
+                with HTTPClient.open(req, timeout=10) as resp:
                     data = json.loads(resp.read().decode("utf-8"))
             except Exception as e:
                 logger.debug(f"[auto-update] Version check failed: {e}")
@@ -1235,14 +3769,13 @@ class ReachLinkAgent:
                 headers={
                     "Authorization": f"Bearer {self.config.token}",
                     "X-Printer-Id": self.config.printer_id,
-                    "User-Agent": f"reach-link-agent/{AGENT_VERSION}",
                 },
             )
 
             current_script = _os.path.abspath(__file__)
             tmp_path = current_script + ".update_tmp"
             try:
-                with urlopen(dl_req, timeout=30) as resp:
+                with HTTPClient.open(dl_req, timeout=30) as resp:
                     content = resp.read()
                 if len(content) < 500:
                     logger.warning("[auto-update] Downloaded file too small — aborting update")
@@ -1264,7 +3797,7 @@ class ReachLinkAgent:
                     f"[auto-update] Updated to v{latest_version_str}. "
                     "Exiting so the process manager can restart with the new version."
                 )
-                sys.exit(0)
+                exit_agent(ExitReason.UPDATED, f"v{latest_version_str}")
             except Exception as e:
                 logger.error(f"[auto-update] Failed to replace script: {e}")
                 try:
@@ -1275,8 +3808,55 @@ class ReachLinkAgent:
         except Exception as e:
             logger.warning(f"[auto-update] Unexpected error during update check: {e}")
 
-    async def run(self):
-        """Main agent loop."""
+    def run_once(self) -> bool:
+        """Register, send one telemetry sample and print the relay's replies.
+
+        For cron jobs and smoke tests (--once / REACH_LINK_ONCE=1): no loop,
+        no health server, no update check and no batching. Returns False if
+        registration or telemetry failed.
+        """
+        now = time.time()
+        try:
+            self._detect_make_model()
+            register_response = self.relay.register_heartbeat(
+                0, version=AGENT_VERSION, printer_limits=self.moonraker.get_printer_limits()
+            )
+            if register_response:
+                self._apply_rotated_token(register_response)
+            telemetry_ok = False
+            moonraker_status = self.moonraker.get_status()
+            if moonraker_status:
+                moonraker_status["recent_jobs"] = self.recent_jobs.get(now)
+                moonraker_status["filament"] = self.filament.get(now)
+                moonraker_status["job_queue"] = self.moonraker.get_job_queue()
+                moonraker_status["stats"] = self.job_stats.get(now)
+                telemetry_ok = self.relay.send_telemetry(
+                    self.relay.build_telemetry_payload(moonraker_status)
+                )
+            else:
+                logger.error("Moonraker snapshot unavailable; telemetry not sent")
+        except ValueError as e:
+            if str(e) != "TOKEN_REVOKED":
+                raise
+            logger.critical("Token has been revoked by server")
+            register_response, telemetry_ok = None, False
+
+        print(json.dumps({
+            "printerId": self.config.printer_id,
+            "register": register_response,
+            "telemetry": self.relay.last_telemetry_response if telemetry_ok else None,
+        }, indent=2))
+        return bool(register_response) and telemetry_ok
+
+    async def run(self, standalone: bool = True):
+        """Main agent loop.
+
+        `standalone` is False when an AgentFleet runs this agent alongside
+        others; the fleet then owns signal handling, the update check and
+        the health server, and logTail carries only lines from this agent's thread.
+        """
+        if not standalone:
+            self.log_tail_thread = threading.current_thread().name
         logger.info(f"reach-link agent starting (version {AGENT_VERSION})")
         logger.info(
             f"relay_url={self.config.relay_url}, "
@@ -1291,18 +3871,57 @@ class ReachLinkAgent:
         
         logger.info("Relay command queue mode enabled")
 
-        # Check for updates before entering the main loop
-        self._check_for_update()
+        health_server = None
+        if standalone:
+            # Bind first so a taken port fails startup before anything else runs
+            health_server = HealthServer(self, self.config.health_addr)
+            health_server.start(required=not self.config.health_optional)
 
-        self.setup_signal_handlers()
+            # Check for updates before entering the main loop
+            self._check_for_update()
+
+            self.setup_signal_handlers()
+
+        if self.config.watchdog_multiplier > 0:
+            threading.Thread(
+                target=self._watchdog, name=f"{threading.current_thread().name}-watchdog", daemon=True
+            ).start()
+
+        # Cleanup on the way out, in this order, bounded by REACH_LINK_SHUTDOWN_TIMEOUT
+        self.shutdown.register("telemetry batch flush", self._flush_batch_on_shutdown)
+        if self.stream:
+            self.shutdown.register("relay stream", self.stream.close)
+        self.shutdown.register("shutdown G-code", self._run_shutdown_gcode)
+        if health_server:
+            self.shutdown.register("health server", health_server.stop)
         
         while not self.shutdown_event.is_set():
             try:
                 now = time.time()
                 uptime = int(now - self.start_time)
+                self._mark_progress()
+
+                if self.reload_requested:
+                    self._apply_config_reload()
                 
-                # Heartbeat to HTTP relay
-                if now - self.last_heartbeat >= self.config.heartbeat_interval:
+                telemetry_interval = self.telemetry_pacer.interval(self.config.telemetry_interval)
+                telemetry_due = now - self.last_telemetry >= telemetry_interval
+
+                # Heartbeat to HTTP relay. Until one registration has
+                # succeeded, retry on a short cadence so a long configured or
+                # server-provided interval can't leave us looking offline
+                # after a restart. With a shared cadence the heartbeat instead
+                # rides every Nth telemetry tick (every tick until it succeeds).
+                if self.cadence:
+                    heartbeat_due = telemetry_due and (
+                        self.cadence.next_tick() or self.last_heartbeat_ok is None
+                    )
+                else:
+                    heartbeat_interval = self.config.heartbeat_interval
+                    if self.last_heartbeat_ok is None:
+                        heartbeat_interval = min(heartbeat_interval, _STARTUP_REGISTER_RETRY_SECS)
+                    heartbeat_due = now - self.last_heartbeat >= heartbeat_interval
+                if heartbeat_due:
                     if not self.token_revoked:
                         try:
                             heartbeat_payload = {
@@ -1311,35 +3930,63 @@ class ReachLinkAgent:
                                 "uptime": uptime,
                                 "version": AGENT_VERSION,
                             }
-                            heartbeat_response = self.relay.register_heartbeat(uptime, version=AGENT_VERSION)
+                            self._detect_make_model()
+                            heartbeat_response = self.relay.register_heartbeat(
+                                uptime,
+                                version=AGENT_VERSION,
+                                last_ok=self.last_heartbeat_ok,
+                                printer_limits=self.moonraker.get_printer_limits(),
+                            )
                             if heartbeat_response:
-                                # Persist rotated token if the server issued one
-                                new_token = str(heartbeat_response.get("rotatedToken", "")).strip()
-                                if new_token:
-                                    self.config.token = new_token
-                                    self.relay.token = new_token
-                                    self.config.persist_state()
-                                    logger.info("Received and persisted rotated reach-link token after first heartbeat")
+                                self.last_heartbeat_ok = now
+                                self._apply_rotated_token(heartbeat_response)
                                 # Respect the server's requested check-in interval
-                                next_check_in = heartbeat_response.get("nextCheckIn")
-                                if next_check_in and isinstance(next_check_in, (int, float)) and int(next_check_in) > 0:
-                                    self.config.heartbeat_interval = int(next_check_in)
+                                self._apply_relay_intervals(heartbeat_response)
                         except ValueError as e:
                             if str(e) == "TOKEN_REVOKED":
                                 logger.critical("Token has been revoked by server. Agent will shut down.")
                                 self.token_revoked = True
-                                self.shutdown_event.set()
+                                self.shutdown.trigger()
                     
                     self.last_heartbeat = now
+                    self._mark_progress()
                 
+                # Between telemetry cycles, probe for errors so a critical
+                # one goes out now instead of waiting for the next cycle.
+                telemetry_now = False
+                if (
+                    now - self.last_error_poll >= _ERROR_POLL_SECS
+                    and not self.token_revoked
+                    and not self.in_maintenance(now)
+                ):
+                    self.last_error_poll = now
+                    if self._poll_critical_errors():
+                        logger.warning("Critical printer error detected; sending telemetry immediately")
+                        telemetry_now = True
+
                 # Telemetry
-                if now - self.last_telemetry >= self.config.telemetry_interval:
+                if telemetry_now or telemetry_due:
                     if not self.token_revoked:
                         try:
                             moonraker_status = self.moonraker.get_status()
                             if moonraker_status:
+                                moonraker_status["recent_jobs"] = self.recent_jobs.get(now)
+                                moonraker_status["filament"] = self.filament.get(now)
+                                moonraker_status["job_queue"] = self.moonraker.get_job_queue()
+                                moonraker_status["stats"] = self.job_stats.get(now)
+                                job = moonraker_status.get("job")
+                                moonraker_status["job_event"] = job_end_event(self.last_job_state, job)
+                                self.last_job_state = (job or {}).get("state")
+                                self.error_queue.push(moonraker_status.get("errors") or [])
+                                queued_errors = self.error_queue.drain()
+                                if self.in_maintenance(now):
+                                    moonraker_status["maintenance"] = True
+                                    moonraker_status["errors"] = []
+                                else:
+                                    moonraker_status["errors"] = self.error_dedup.filter(queued_errors, now)
                                 # Send to HTTP relay
-                                self.relay.send_telemetry(moonraker_status)
+                                if self._send_telemetry(moonraker_status):
+                                    self.last_telemetry_ok = now
                                 
                                 # Also update Firebase RTDB (cloud command queue)
                                 if self.firebase:
@@ -1369,8 +4016,12 @@ class ReachLinkAgent:
                             if str(e) == "TOKEN_REVOKED":
                                 logger.critical("Token has been revoked by server. Agent will shut down.")
                                 self.token_revoked = True
-                                self.shutdown_event.set()
+                                self.shutdown.trigger()
+                    self.telemetry_pacer.record(
+                        time.time() - now, self.config.telemetry_interval, self.config.max_interval
+                    )
                     self.last_telemetry = now
+                    self._mark_progress()
                 
                 # Webcam snapshot (only when a viewer is active in the dashboard)
                 if now - self.last_webcam_capture >= self.config.webcam_snapshot_interval:
@@ -1385,6 +4036,7 @@ class ReachLinkAgent:
                         except Exception as e:
                             logger.debug(f"Webcam snapshot error: {e}")
                     self.last_webcam_capture = now
+                    self._mark_progress()
 
                 # Process pending commands from relay queue.
                 # The pull endpoint long-polls for up to 25 s so this loop
@@ -1401,6 +4053,9 @@ class ReachLinkAgent:
 
                     self.last_command_poll = now
                 
+                if self.stream and not self.token_revoked:
+                    self._poll_stream()
+
                 # Sleep briefly to avoid busy-waiting
                 await asyncio.sleep(1)
             
@@ -1408,8 +4063,158 @@ class ReachLinkAgent:
                 logger.error(f"Error in agent loop: {e}")
                 await asyncio.sleep(5)
         
+        self.shutdown.finish()
         logger.info("reach-link agent stopped")
 
+    def send_exit_notice(self, reason: ExitReason, detail: str = "") -> None:
+        """Exit notifier (see exit_agent): tell the relay why we're going away.
+
+        Skipped once the token is revoked, since the relay would reject it.
+        """
+        if self.token_revoked or reason is ExitReason.TOKEN_REVOKED:
+            return
+        self.relay.send_disconnect(reason, detail)
+
+    def _mark_progress(self) -> None:
+        """Record that the loop is still advancing (see _watchdog)."""
+        self.last_progress = time.time()
+
+    def _watchdog(self) -> None:
+        """Exit the process if the agent loop stops advancing without erroring.
+
+        The loop stamps last_progress at the start of each pass and after
+        each blocking step (heartbeat, telemetry, webcam, each command pull),
+        so a slow but live pass keeps it fresh and only a step that never
+        returns lets it go stale. Exiting non-zero lets the service manager
+        restart us instead of leaving a silent zombie.
+        """
+        while not self.shutdown_event.wait(_WATCHDOG_CHECK_SECS):
+            interval = max(
+                self.config.heartbeat_interval,
+                self.telemetry_pacer.interval(self.config.telemetry_interval),
+            )
+            stalled = loop_stall_secs(
+                time.time(), self.last_progress, interval, self.config.watchdog_multiplier
+            )
+            if stalled is not None:
+                exit_agent(
+                    ExitReason.WATCHDOG,
+                    f"agent loop for printer {self.config.printer_id} made no progress for {int(stalled)}s",
+                    hard=True,
+                )
+
+    def _flush_batch_on_shutdown(self) -> None:
+        """Don't lose a partially filled batch on shutdown."""
+        if self.telemetry_batch and not self.token_revoked:
+            self._flush_telemetry_batch()
+
+    def _run_shutdown_gcode(self) -> None:
+        """Send REACH_LINK_SHUTDOWN_GCODE (e.g. heaters off) on the way out."""
+        script = self.config.shutdown_gcode
+        if not script:
+            return
+        logger.info(f"Running shutdown G-code: {script}")
+        if self.moonraker.execute_gcode(script, timeout=_SHUTDOWN_GCODE_TIMEOUT_SECS):
+            logger.info("Shutdown G-code completed")
+        else:
+            logger.warning(
+                f"Shutdown G-code failed or did not finish within {_SHUTDOWN_GCODE_TIMEOUT_SECS}s"
+            )
+
+def install_signal_handlers(agents: list) -> None:
+    """SIGTERM/SIGINT stop every agent; SIGHUP asks each to reload its intervals;
+    SIGUSR2 toggles maintenance mode."""
+    def signal_handler(signum, frame):
+        logger.info(f"Received signal {signum}; shutting down...")
+        for agent in agents:
+            agent.shutdown.trigger()
+
+    signal.signal(signal.SIGTERM, signal_handler)
+    signal.signal(signal.SIGINT, signal_handler)
+
+    def reload_handler(signum, frame):
+        # Applied on the next loop pass, outside the signal context.
+        for agent in agents:
+            agent.reload_requested = True
+
+    if hasattr(signal, "SIGHUP"):
+        signal.signal(signal.SIGHUP, reload_handler)
+
+    def maintenance_handler(signum, frame):
+        for agent in agents:
+            agent.set_maintenance({})
+
+    if hasattr(signal, "SIGUSR2"):
+        signal.signal(signal.SIGUSR2, maintenance_handler)
+
+
+class AgentFleet:
+    """Multi-printer mode: one agent loop per printer in a single process.
+
+    Each printer gets its own ReachLinkAgent (own Moonraker, token and
+    intervals) on its own thread; they share the HTTP client settings and
+    keep-alive pool, the signal handlers and one health server whose
+    /health response is keyed by printer id.
+    """
+
+    def __init__(self, config: Config):
+        self.config = config
+        self.agents = [ReachLinkAgent(config.for_printer(entry)) for entry in config.printers]
+
+    def health_status(self) -> Dict[str, Any]:
+        return {
+            "status": "ok",
+            "version": AGENT_VERSION,
+            "printers": {agent.config.printer_id: agent.health_status() for agent in self.agents},
+        }
+
+    def control_tokens(self) -> list:
+        """Any printer's token authorizes health server POSTs."""
+        return [token for agent in self.agents for token in agent.control_tokens()]
+
+    def set_maintenance(self, request: Dict[str, Any]) -> Dict[str, Any]:
+        """Apply a /maintenance request to one printer ("printerId") or all of them."""
+        printer_id = request.get("printerId")
+        agents = [a for a in self.agents if printer_id in (None, a.config.printer_id)]
+        if not agents:
+            raise ValueError(f"unknown printerId: {printer_id}")
+        return {"printers": [agent.set_maintenance(request) for agent in agents]}
+
+    def run(self) -> None:
+        logger.info(f"reach-link multi-printer mode: {len(self.agents)} printer(s)")
+        health_server = HealthServer(self, self.config.health_addr)
+        health_server.start(required=not self.config.health_optional)
+
+        # One update check covers the whole process (it exits to restart).
+        self.agents[0]._check_for_update()
+        install_signal_handlers(self.agents)
+
+        threads = []
+        for agent in self.agents:
+            thread = threading.Thread(
+                target=lambda a=agent: asyncio.run(a.run(standalone=False)),
+                name=f"printer-{agent.config.printer_id}",
+                daemon=True,
+            )
+            thread.start()
+            threads.append(thread)
+
+        # Each agent runs its own cleanup; the fleet waits for them (within
+        # the same deadline) and then stops the shared health server.
+        shutdown = ShutdownCoordinator(self.config.shutdown_timeout)
+        for agent, thread in zip(self.agents, threads):
+            shutdown.register(f"printer {agent.config.printer_id}", thread.join)
+        shutdown.register("health server", health_server.stop)
+
+        # Poll so the main thread keeps servicing signals.
+        while any(thread.is_alive() for thread in threads) and not all(
+            agent.shutdown_event.is_set() for agent in self.agents
+        ):
+            time.sleep(1)
+
+        shutdown.finish()
+        logger.info("reach-link multi-printer agent stopped")
+
 # ============================================================================
 # Entry Point
 # ============================================================================
@@ -1425,28 +4230,41 @@ def main():
             "Exiting to prevent duplicate instance.",
             file=sys.stderr,
         )
-        sys.exit(0)
+        exit_agent(ExitReason.ALREADY_RUNNING, f"PID {existing_pid}")
 
     try:
         # Load config
         config = Config()
         
         # Setup logging
-        setup_logging(config.log_file)
+        setup_logging(config.log_file, with_thread=bool(config.printers))
+        HTTPClient.configure(config)
         
         # Run agent
-        agent = ReachLinkAgent(config)
-        asyncio.run(agent.run())
+        if config.once or "--once" in sys.argv[1:]:
+            agents = AgentFleet(config).agents if config.printers else [ReachLinkAgent(config)]
+            results = [agent.run_once() for agent in agents]
+            exit_agent(ExitReason.STOPPED if all(results) else ExitReason.ONCE_FAILED)
+        fleet = AgentFleet(config) if config.printers else None
+        agents = fleet.agents if fleet else [ReachLinkAgent(config)]
+        # Whatever path ends the process, tell the relay why
+        _EXIT_NOTIFIERS.extend(agent.send_exit_notice for agent in agents)
+        if fleet:
+            fleet.run()
+        else:
+            asyncio.run(agents[0].run())
+        if all(agent.token_revoked for agent in agents):
+            exit_agent(ExitReason.TOKEN_REVOKED)
+        exit_agent(ExitReason.STOPPED)
     
     except KeyboardInterrupt:
-        logger.info("Interrupted by user")
-        sys.exit(0)
+        exit_agent(ExitReason.STOPPED, "interrupted by user")
+    except HealthBindError as e:
+        exit_agent(ExitReason.HEALTH_BIND_FAILED, str(e))
     except ValueError as e:
-        logger.error(f"Configuration error: {e}")
-        sys.exit(1)
+        exit_agent(ExitReason.CONFIG_ERROR, str(e))
     except Exception as e:
-        logger.error(f"Fatal error: {e}")
-        sys.exit(1)
+        exit_agent(ExitReason.FATAL_ERROR, str(e))
 
 if __name__ == "__main__":
     main()
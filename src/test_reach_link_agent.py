@@ -0,0 +1,2065 @@
+"""Unit tests for the Python agent (stdlib unittest only, like the agent).
+
+Run from the repository root:
+
+    python3 -m unittest discover -s src
+"""
+
+import asyncio
+import contextlib
+import errno
+import importlib.util
+import io
+import json
+import logging
+import os
+import socket
+import ssl
+import sys
+import tempfile
+import threading
+import time
+import unittest
+from http.server import BaseHTTPRequestHandler, ThreadingHTTPServer
+from typing import Optional
+from unittest import mock
+from urllib.error import HTTPError, URLError
+
+sys.dont_write_bytecode = True
+
+# The agent's file name isn't a valid module name, so load it by path.
+_AGENT_PATH = os.path.join(os.path.dirname(os.path.abspath(__file__)), "reach-link-agent.py")
+_spec = importlib.util.spec_from_file_location("reach_link_agent", _AGENT_PATH)
+agent = importlib.util.module_from_spec(_spec)
+_spec.loader.exec_module(agent)
+# Keep expected warnings out of the test output (assertLogs still sees them).
+agent.logger.addHandler(logging.NullHandler())
+agent.logger.propagate = False
+
+# Minimal environment for a valid single-printer Config.
+_BASE_ENV = {
+    "REACH_LINK_RELAY": "https://relay.example.com",
+    "REACH_LINK_TOKEN": "test-token",
+    "REACH_LINK_PRINTER_ID": "test-printer",
+    "REACH_LINK_STATE_FILE": "",
+}
+
+
+def make_config(**env) -> "agent.Config":
+    """Config built from _BASE_ENV plus `env` only (no real environment or .env file)."""
+    values = dict(_BASE_ENV, **env)
+    with mock.patch.dict(os.environ, values, clear=True), \
+            mock.patch.object(agent.Config, "_env_file_path", return_value="/nonexistent/.env"):
+        return agent.Config()
+
+
+def make_relay(**env) -> "agent.RelayClient":
+    """RelayClient for a config built by make_config."""
+    config = make_config(**env)
+    return agent.RelayClient(config.relay_url, config.token, config.printer_id, config)
+
+
+def make_agent(**env) -> "agent.ReachLinkAgent":
+    """Agent (watchdog and slow caches off) whose relay and Moonraker clients are mocks."""
+    env = dict({
+        "REACH_LINK_WATCHDOG_MULTIPLIER": "0",
+        "REACH_LINK_HISTORY_INTERVAL": "0",
+        "REACH_LINK_FILAMENT_INTERVAL": "0",
+    }, **env)
+    reach_agent = agent.ReachLinkAgent(make_config(**env))
+    reach_agent.relay = mock.create_autospec(agent.RelayClient, instance=True)
+    reach_agent.relay.register_heartbeat.return_value = {"ok": True}
+    reach_agent.relay.send_telemetry.return_value = True
+    reach_agent.relay.pull_command.return_value = None
+    reach_agent.relay.last_telemetry_response = {"ok": True}
+    reach_agent.moonraker = mock.create_autospec(agent.MoonrakerClient, instance=True)
+    reach_agent.moonraker.get_status.return_value = None
+    reach_agent.moonraker.get_errors.return_value = []
+    reach_agent.moonraker.get_printer_limits.return_value = None
+    reach_agent.moonraker.get_machine_hint.return_value = None
+    return reach_agent
+
+
+def run_loop(reach_agent, passes: int = 1, events: Optional[list] = None) -> list:
+    """Run the agent loop for `passes` passes, appending "sleep" to `events`.
+
+    Returns the sleep durations the loop asked for (1s per pass, 5s after an error).
+    """
+    sleeps = []
+
+    async def fake_sleep(secs):
+        sleeps.append(secs)
+        if events is not None:
+            events.append("sleep")
+        if len(sleeps) >= passes:
+            reach_agent.shutdown.trigger()
+
+    with mock.patch.object(agent.asyncio, "sleep", fake_sleep):
+        asyncio.run(reach_agent.run(standalone=False))
+    return sleeps
+
+
+class RecordingServer:
+    """Local HTTP server recording each request; `respond(request)` picks the reply.
+
+    `request` is a dict with method, path, headers and body; `respond` returns
+    (status, headers, body bytes). Use as a context manager; `url` is its base.
+    """
+
+    def __init__(self, respond):
+        self.requests: list = []
+        server = self
+
+        class Handler(BaseHTTPRequestHandler):
+            def _handle(self):
+                length = int(self.headers.get("Content-Length") or 0)
+                request = {
+                    "method": self.command,
+                    "path": self.path,
+                    "headers": self.headers,
+                    "body": self.rfile.read(length) if length else b"",
+                }
+                server.requests.append(request)
+                status, headers, body = respond(request)
+                self.send_response(status)
+                for name, value in headers.items():
+                    self.send_header(name, value)
+                self.send_header("Content-Length", str(len(body)))
+                self.end_headers()
+                self.wfile.write(body)
+
+            do_GET = do_POST = _handle
+
+            def log_message(self, format, *args):
+                pass
+
+        self.httpd = ThreadingHTTPServer(("127.0.0.1", 0), Handler)
+        self.url = f"http://127.0.0.1:{self.httpd.server_address[1]}"
+
+    def __enter__(self):
+        threading.Thread(target=self.httpd.serve_forever, daemon=True).start()
+        return self
+
+    def __exit__(self, *exc):
+        self.httpd.shutdown()
+        self.httpd.server_close()
+
+
+def json_reply(body, status: int = 200):
+    """RecordingServer reply carrying `body` as JSON."""
+    return status, {"Content-Type": "application/json"}, json.dumps(body).encode("utf-8")
+
+
+def json_response(body, status: int = 200, headers=None) -> "agent.PooledResponse":
+    """A urlopen()-shaped response carrying `body` as JSON."""
+    return agent.PooledResponse(status, "OK", headers or {}, json.dumps(body).encode("utf-8"))
+
+
+def moonraker_snapshot(status: dict) -> dict:
+    """MoonrakerClient.get_status() for a canned `/printer/objects/query` status."""
+    client = agent.MoonrakerClient("http://moonraker.local:7125")
+    client.sensor_objects = []
+    with mock.patch.object(agent.HTTPClient, "get_json", return_value={"result": {"status": status}}):
+        return client.get_status()
+
+
+class MinTlsTests(unittest.TestCase):
+    def test_defaults_to_tls_1_2(self):
+        self.assertEqual(make_config().min_tls, "1.2")
+
+    def test_accepts_tls_1_3(self):
+        self.assertEqual(make_config(REACH_LINK_MIN_TLS="1.3").min_tls, "1.3")
+
+    def test_rejects_unsupported_version(self):
+        for value in ("1.1", "1.0", "tls1.2", ""):
+            with self.subTest(value=value):
+                with self.assertRaises(ValueError):
+                    make_config(REACH_LINK_MIN_TLS=value)
+
+
+class RecentJobsTests(unittest.TestCase):
+    def test_parses_history_list(self):
+        response = {
+            "result": {
+                "count": 2,
+                "jobs": [
+                    {
+                        "job_id": "000002",
+                        "filename": "benchy.gcode",
+                        "status": "completed",
+                        "print_duration": 1834.6,
+                        "total_duration": 1901.2,
+                        "end_time": 1700000500.0,
+                    },
+                    {
+                        "job_id": "000001",
+                        "filename": "cube.gcode",
+                        "status": "cancelled",
+                        "print_duration": None,
+                        "total_duration": 12.9,
+                        "end_time": 1700000100.0,
+                    },
+                ],
+            }
+        }
+        self.assertEqual(agent.parse_recent_jobs(response), [
+            {
+                "filename": "benchy.gcode",
+                "status": "completed",
+                "printDuration": 1834,
+                "totalDuration": 1901,
+                "endTime": 1700000500.0,
+            },
+            {
+                "filename": "cube.gcode",
+                "status": "cancelled",
+                "printDuration": 0,
+                "totalDuration": 12,
+                "endTime": 1700000100.0,
+            },
+        ])
+
+    def test_empty_history(self):
+        self.assertEqual(agent.parse_recent_jobs({"result": {"jobs": []}}), [])
+
+
+class JobStateMappingTests(unittest.TestCase):
+    def test_moonraker_states(self):
+        for state, expected in (
+            ("standby", "idle"),
+            ("printing", "printing"),
+            ("paused", "paused"),
+            ("complete", "complete"),
+            ("cancelled", "cancelled"),
+            ("error", "error"),
+            ("failed", "error"),
+        ):
+            with self.subTest(state=state):
+                self.assertEqual(agent.map_job_state(state), expected)
+
+    def test_cancel_with_an_error_message_is_an_error(self):
+        self.assertEqual(agent.map_job_state("cancelled", "  "), "cancelled")
+        self.assertEqual(agent.map_job_state("cancelled", "Heater extruder not heating"), "error")
+
+    def test_unknown_state_falls_back_and_warns(self):
+        agent._UNMAPPED_JOB_STATES.discard("bogus_state")
+        with self.assertLogs(agent.logger, level="WARNING") as logs:
+            self.assertEqual(agent.map_job_state("bogus_state"), "unknown")
+        self.assertIn("bogus_state", logs.output[0])
+
+    def test_unknown_state_warns_only_once(self):
+        agent.map_job_state("another_bogus_state")
+        with mock.patch.object(agent.logger, "warning") as warning:
+            self.assertEqual(agent.map_job_state("another_bogus_state"), "unknown")
+        warning.assert_not_called()
+
+
+class BedMeshTests(unittest.TestCase):
+    def test_loaded_profile_reports_name_and_range(self):
+        status = {
+            "bed_mesh": {
+                "profile_name": "default",
+                "probed_matrix": [
+                    [0.012, -0.034, 0.051],
+                    [0.003, 0.0, -0.041],
+                    [0.067, 0.021, -0.008],
+                ],
+            }
+        }
+        self.assertEqual(agent.parse_bed_mesh(status), {
+            "loaded": True,
+            "profile": "default",
+            "range": 0.108,
+        })
+
+    def test_no_profile_loaded(self):
+        status = {"bed_mesh": {"profile_name": "", "probed_matrix": [[]]}}
+        self.assertEqual(agent.parse_bed_mesh(status), {"loaded": False, "profile": None, "range": None})
+
+    def test_bed_mesh_not_configured(self):
+        self.assertIsNone(agent.parse_bed_mesh({}))
+
+
+class MoonrakerSnapshotRetryTests(unittest.TestCase):
+    def test_first_query_failure_then_success_returns_snapshot(self):
+        status = {
+            "extruder": {"temperature": 210.3, "target": 210.0, "power": 0.42},
+            "heater_bed": {"temperature": 60.1, "target": 60.0, "power": 0.18},
+            "print_stats": {"state": "printing", "filename": "benchy.gcode"},
+            "webhooks": {"state": "ready"},
+        }
+        calls = []
+
+        def fake_open(req, timeout):
+            url = req.full_url if hasattr(req, "full_url") else req
+            if "/printer/objects/list" in url:
+                return json_response({"result": {"objects": ["extruder", "heater_bed"]}})
+            if "/server/files/metadata" in url:
+                return json_response({"result": {"filename": "benchy.gcode"}})
+            calls.append(url)
+            if len(calls) == 1:
+                raise agent.URLError(ConnectionResetError(104, "Connection reset by peer"))
+            return json_response({"result": {"status": status}})
+
+        client = agent.MoonrakerClient("http://moonraker.local:7125")
+        with mock.patch.object(agent.HTTPClient, "open", side_effect=fake_open), \
+                mock.patch.object(agent.time, "sleep"):
+            snapshot = client.get_status()
+
+        self.assertEqual(len(calls), 2)
+        self.assertIsNotNone(snapshot)
+        self.assertEqual(snapshot["temperatures"]["nozzle"], 210.3)
+        self.assertEqual(snapshot["job"]["state"], "printing")
+
+
+class RelayAuthTests(unittest.TestCase):
+    def test_parses_multiple_relay_headers(self):
+        headers = agent.Config._parse_relay_headers({
+            "REACH_LINK_RELAY_HEADER": "X-Tenant: farm-1",
+            "REACH_LINK_RELAY_HEADER_1": "CF-Access-Client-Id: abc.access",
+            "REACH_LINK_RELAY_HEADER_2": "CF-Access-Client-Secret:  s3cr3t ",
+            "REACH_LINK_RELAYHEADER": "Ignored: yes",
+        })
+        self.assertEqual(headers, {
+            "X-Tenant": "farm-1",
+            "CF-Access-Client-Id": "abc.access",
+            "CF-Access-Client-Secret": "s3cr3t",
+        })
+
+    def test_rejects_malformed_relay_headers(self):
+        for raw in ("no-colon", ": value", "Key:", "Bad Key: value", "Key: a\nb"):
+            with self.subTest(raw=raw):
+                with self.assertRaises(ValueError):
+                    agent.Config._parse_relay_headers({"REACH_LINK_RELAY_HEADER_1": raw})
+
+    def test_basic_credentials_are_sent_alongside_bearer(self):
+        sent = []
+
+        def fake_open(req, timeout):
+            sent.append(req)
+            return json_response({"ok": True})
+
+        with mock.patch.object(agent.HTTPClient, "open", side_effect=fake_open):
+            agent.HTTPClient.post_json(
+                "https://relay.example.com/api/reach-link/register", {},
+                token="test-token", basic_auth=("proxy-user", "proxy-pass"), max_retries=1,
+            )
+        self.assertEqual(
+            sent[0].get_header("Authorization"),
+            agent.HTTPClient.basic_auth_header("proxy-user", "proxy-pass"),
+        )
+        self.assertEqual(sent[0].get_header("X-reach-token"), "test-token")
+        self.assertIsNone(sent[0].get_header("Proxy-authorization"))
+
+    def test_relay_client_moves_the_token_aside_for_basic_credentials(self):
+        # Not Proxy-Authorization: urllib moves that onto the CONNECT to an
+        # https_proxy, so the relay's own auth proxy would never see it.
+        relay = make_relay(
+            REACH_LINK_RELAY_BASIC_USER="proxy-user", REACH_LINK_RELAY_BASIC_PASS="proxy-pass"
+        )
+        headers = relay.auth_headers()
+        self.assertEqual(headers["Authorization"], agent.HTTPClient.basic_auth_header("proxy-user", "proxy-pass"))
+        self.assertEqual(headers["X-Reach-Token"], "test-token")
+        self.assertNotIn("Proxy-Authorization", headers)
+
+    def test_bearer_only_without_basic_credentials(self):
+        self.assertEqual(make_relay().auth_headers(), {"Authorization": "Bearer test-token"})
+
+    def _post_401(self, headers, token="test-token"):
+        error = agent.HTTPError(
+            "https://relay.example.com/api/reach-link/register", 401, "Unauthorized", headers, None
+        )
+        with mock.patch.object(agent.HTTPClient, "open", side_effect=error):
+            return agent.HTTPClient.post_json(
+                "https://relay.example.com/api/reach-link/register", {}, token=token, max_retries=1
+            )
+
+    def test_relay_401_means_token_revoked(self):
+        with self.assertRaisesRegex(ValueError, "TOKEN_REVOKED"):
+            self._post_401({"Content-Type": "application/json"})
+
+    def test_auth_proxy_401_is_not_token_revocation(self):
+        self.assertIsNone(self._post_401({"WWW-Authenticate": 'Basic realm="relay"'}))
+
+    def test_401_without_a_token_is_not_token_revocation(self):
+        self.assertIsNone(self._post_401({}, token=None))
+
+
+class HealthBindTests(unittest.TestCase):
+    def test_loopback_bind(self):
+        config = make_config(REACH_LINK_HEALTH_BIND="127.0.0.1", REACH_LINK_HEALTH_PORT="9090")
+        self.assertEqual(config.health_addr, ("127.0.0.1", 9090))
+
+    def test_rejects_invalid_bind_address(self):
+        for value in ("localhost", "127.0.0.256", "0.0.0.0:8080"):
+            with self.subTest(value=value):
+                with self.assertRaises(ValueError):
+                    make_config(REACH_LINK_HEALTH_BIND=value)
+
+    def test_serves_health_on_loopback(self):
+        owner = mock.Mock()
+        owner.health_status.return_value = {"status": "ok"}
+        server = agent.HealthServer(owner, ("127.0.0.1", 0))
+        self.assertTrue(server.start())
+        try:
+            port = server.httpd.server_address[1]
+            with agent.urlopen(f"http://127.0.0.1:{port}/health", timeout=5) as response:
+                self.assertEqual(json.loads(response.read()), {"status": "ok"})
+        finally:
+            server.stop()
+
+
+class StartupRegistrationTests(unittest.TestCase):
+    def test_registers_before_the_first_sleep(self):
+        reach_agent = make_agent(REACH_LINK_HEARTBEAT_INTERVAL="3600")
+        events = []
+        reach_agent.relay.register_heartbeat.side_effect = (
+            lambda *args, **kwargs: events.append("register") or {"ok": True}
+        )
+        run_loop(reach_agent, passes=2, events=events)
+        self.assertEqual(events[0], "register")
+        self.assertEqual(events.count("register"), 1)
+
+
+class SpoolmanTests(unittest.TestCase):
+    def test_active_spool_with_remaining_grams(self):
+        client = agent.MoonrakerClient("http://moonraker.local:7125")
+        spool = {
+            "result": {
+                "id": 7,
+                "remaining_weight": 612.348,
+                "filament": {"name": "Galaxy Black", "material": "PLA"},
+            }
+        }
+        with mock.patch.object(
+            agent.HTTPClient, "get_json", return_value={"result": {"spool_id": 7}}
+        ), mock.patch.object(agent.HTTPClient, "post_json", return_value=spool) as post_json:
+            filament = client.get_active_filament()
+        self.assertEqual(filament, {"spoolId": 7, "material": "PLA", "remainingWeight": 612.3})
+        self.assertEqual(post_json.call_args[0][1]["path"], "/v1/spool/7")
+
+    def test_no_active_spool(self):
+        client = agent.MoonrakerClient("http://moonraker.local:7125")
+        with mock.patch.object(agent.HTTPClient, "get_json", return_value={"result": {"spool_id": None}}):
+            self.assertIsNone(client.get_active_filament())
+
+
+class IntervalClampTests(unittest.TestCase):
+    def setUp(self):
+        self.config = make_config(REACH_LINK_MIN_INTERVAL="5", REACH_LINK_MAX_INTERVAL="600")
+
+    def test_below_minimum_is_raised(self):
+        with self.assertLogs(agent.logger, level="WARNING"):
+            self.assertEqual(self.config.clamp_interval("nextCheckIn", 1), 5)
+
+    def test_above_maximum_is_lowered(self):
+        with self.assertLogs(agent.logger, level="WARNING"):
+            self.assertEqual(self.config.clamp_interval("nextDataInterval", 86400), 600)
+
+    def test_in_range_is_kept(self):
+        self.assertEqual(self.config.clamp_interval("nextCheckIn", 30), 30)
+
+    def test_missing_or_non_positive_is_ignored(self):
+        for value in (None, 0, -10, "30", True):
+            with self.subTest(value=value):
+                self.assertIsNone(self.config.clamp_interval("nextCheckIn", value))
+
+    def test_rejects_inverted_bounds(self):
+        with self.assertRaises(ValueError):
+            make_config(REACH_LINK_MIN_INTERVAL="60", REACH_LINK_MAX_INTERVAL="30")
+
+
+class TelemetrySequenceTests(unittest.TestCase):
+    def test_successive_sends_carry_consecutive_seq(self):
+        relay = make_relay()
+        with mock.patch.object(agent.HTTPClient, "post_json", return_value={"ok": True}) as post_json:
+            for _ in range(2):
+                relay.send_telemetry(relay.build_telemetry_payload({}))
+        first, second = (call[0][1]["seq"] for call in post_json.call_args_list)
+        self.assertEqual(second, first + 1)
+
+
+class SystemHealthGuardTests(unittest.TestCase):
+    def test_stalled_collector_yields_none_and_telemetry_still_sends(self):
+        release = threading.Event()
+
+        def stalled_collector(status):
+            release.wait(5)
+            return {"cpuPercent": 1.0}
+
+        client = agent.MoonrakerClient("http://moonraker.local:7125")
+        relay = make_relay()
+        try:
+            with mock.patch.object(agent, "_SYSTEM_HEALTH_TIMEOUT_SECS", 0.1):
+                health = client.collect_system_health_guarded({}, collector=stalled_collector)
+        finally:
+            release.set()
+        self.assertIsNone(health)
+
+        with mock.patch.object(agent.HTTPClient, "post_json", return_value={"ok": True}) as post_json:
+            self.assertTrue(relay.send_telemetry(relay.build_telemetry_payload({"system_health": health})))
+        self.assertIsNone(post_json.call_args[0][1]["systemHealth"])
+
+    def test_raising_collector_yields_none(self):
+        def broken_collector(status):
+            raise KeyError("system_stats")
+
+        client = agent.MoonrakerClient("http://moonraker.local:7125")
+        self.assertIsNone(client.collect_system_health_guarded({}, collector=broken_collector))
+
+    def test_hung_collector_is_not_queued_again(self):
+        release = threading.Event()
+        calls = []
+
+        def hung_collector(status):
+            calls.append(status)
+            release.wait(5)
+            return {"cpuPercent": 1.0}
+
+        client = agent.MoonrakerClient("http://moonraker.local:7125")
+        self.addCleanup(release.set)
+        with mock.patch.object(agent, "_SYSTEM_HEALTH_TIMEOUT_SECS", 0.05):
+            self.assertIsNone(client.collect_system_health_guarded({}, collector=hung_collector))
+            self.assertIsNone(client.collect_system_health_guarded({}, collector=hung_collector))
+            self.assertEqual(len(calls), 1)
+            self.assertEqual(client._health_executor._work_queue.qsize(), 0)
+
+            release.set()
+            client._health_future.result(timeout=1)
+            self.assertEqual(
+                client.collect_system_health_guarded({}, collector=lambda status: {"cpuPercent": 2.0}),
+                {"cpuPercent": 2.0},
+            )
+
+
+class ConfigReloadTests(unittest.TestCase):
+    def setUp(self):
+        tmp = tempfile.TemporaryDirectory()
+        self.addCleanup(tmp.cleanup)
+        self.env_path = os.path.join(tmp.name, ".env")
+
+    def _write_env(self, **values):
+        with open(self.env_path, "w", encoding="utf-8") as f:
+            f.write("".join(f"{key}={value}\n" for key, value in values.items()))
+
+    def _config_and_reload(self, exported, startup_file, reloaded_file):
+        env = dict(_BASE_ENV, **exported)
+        self._write_env(**startup_file)
+        with mock.patch.dict(os.environ, env, clear=True), \
+                mock.patch.object(agent.Config, "_env_file_path", return_value=self.env_path), \
+                contextlib.redirect_stderr(io.StringIO()):
+            config = agent.Config()
+            self._write_env(**reloaded_file)
+            changed = config.reload_intervals()
+        return config, changed
+
+    def test_reload_swaps_intervals_from_env_file(self):
+        config, changed = self._config_and_reload(
+            {},
+            {"REACH_LINK_HEARTBEAT_INTERVAL": "30", "REACH_LINK_TELEMETRY_INTERVAL": "10"},
+            {"REACH_LINK_HEARTBEAT_INTERVAL": "60", "REACH_LINK_TELEMETRY_INTERVAL": "10"},
+        )
+        self.assertEqual(changed, {"heartbeat_interval": 60})
+        self.assertEqual((config.heartbeat_interval, config.telemetry_interval), (60, 10))
+
+    def test_exported_value_still_wins_over_env_file(self):
+        config, changed = self._config_and_reload(
+            {"REACH_LINK_HEARTBEAT_INTERVAL": "45"},
+            {"REACH_LINK_HEARTBEAT_INTERVAL": "30"},
+            {"REACH_LINK_HEARTBEAT_INTERVAL": "99", "REACH_LINK_TELEMETRY_INTERVAL": "20"},
+        )
+        self.assertEqual(changed, {"telemetry_interval": 20})
+        self.assertEqual(config.heartbeat_interval, 45)
+
+    def test_invalid_value_is_ignored(self):
+        config, changed = self._config_and_reload(
+            {}, {}, {"REACH_LINK_TELEMETRY_INTERVAL": "fast"}
+        )
+        self.assertEqual(changed, {})
+        self.assertEqual(config.telemetry_interval, 10)
+
+
+class EtaEstimatorTests(unittest.TestCase):
+    def test_file_position_basis(self):
+        eta = agent.EtaEstimator()
+        # 25% of the file read after 600s printing -> 1800s remaining.
+        self.assertEqual(eta.update(1000.0, 600, {"file_position": 0.25}), (1800, "file_position"))
+
+    def test_too_early_or_unusable_basis_gives_no_eta(self):
+        eta = agent.EtaEstimator()
+        self.assertEqual(eta.update(1000.0, 600, {"file_position": 0.005}), (None, None))
+        self.assertEqual(eta.update(1000.0, 0, {"file_position": 0.5}), (None, None))
+        self.assertEqual(eta.update(1000.0, 600, {"file_position": None}), (None, None))
+
+    def test_prefers_the_steadier_basis(self):
+        eta = agent.EtaEstimator()
+        eta.update(1000.0, 600, {"slicer_progress": 0.5, "file_position": 0.25})
+        # 60s later: file_position's ETA fell by ~60s as expected, while the
+        # slicer's jumped, so file_position is reported.
+        remaining, method = eta.update(
+            1060.0, 660, {"slicer_progress": 0.3, "file_position": 0.268}
+        )
+        self.assertEqual(method, "file_position")
+        self.assertEqual(remaining, int(660 / 0.268 - 660))
+
+    def test_snapshot_reports_virtual_sdcard_eta(self):
+        status = {
+            "print_stats": {"state": "printing", "print_duration": 600, "total_duration": 610},
+            "virtual_sdcard": {"progress": 0.25, "file_position": 2500, "file_size": 10000},
+            "webhooks": {"state": "ready"},
+        }
+        client = agent.MoonrakerClient("http://moonraker.local:7125")
+        client.sensor_objects = []
+        with mock.patch.object(agent.HTTPClient, "get_json", return_value={"result": {"status": status}}):
+            job = client.get_status()["job"]
+        self.assertEqual((job["estimatedTime"], job["etaMethod"]), (1800, "file_position"))
+
+
+class UserAgentTests(unittest.TestCase):
+    def _user_agent_seen(self, **env):
+        with RecordingServer(lambda request: json_reply({"result": {}})) as server, \
+                mock.patch.multiple(agent.HTTPClient, ssl_context=None, user_agent=None, pool=None):
+            agent.HTTPClient.configure(make_config(**env))
+            agent.HTTPClient.get_json(f"{server.url}/server/info", max_retries=1)
+            agent.HTTPClient.post_json(f"{server.url}/api/reach-link/register", {}, max_retries=1)
+        return [request["headers"]["User-Agent"] for request in server.requests]
+
+    def test_default_user_agent(self):
+        self.assertEqual(self._user_agent_seen(), [f"reach-link/{agent.AGENT_VERSION}"] * 2)
+
+    def test_overridden_user_agent(self):
+        self.assertEqual(
+            self._user_agent_seen(REACH_LINK_USER_AGENT="farm-42/reach-link"),
+            ["farm-42/reach-link"] * 2,
+        )
+
+
+class TelemetryBatchTests(unittest.TestCase):
+    def _agent(self, **env):
+        reach_agent = make_agent(**env)
+        reach_agent.relay.build_telemetry_payload.side_effect = (
+            lambda status: {"sample": status.get("sample"), "errors": status.get("errors") or []}
+        )
+        return reach_agent
+
+    def test_batch_is_sent_once_full(self):
+        reach_agent = self._agent(REACH_LINK_BATCH_SIZE="3")
+        results = [reach_agent._send_telemetry({"sample": n}) for n in range(3)]
+        self.assertEqual(results, [None, None, True])
+        reach_agent.relay.send_telemetry.assert_called_once()
+        batch = reach_agent.relay.send_telemetry.call_args[0][0]
+        self.assertEqual([sample["sample"] for sample in batch], [0, 1, 2])
+
+    def test_partial_batch_flushes_at_max_age(self):
+        reach_agent = self._agent(REACH_LINK_BATCH_SIZE="10", REACH_LINK_BATCH_MAX_AGE="60")
+        with mock.patch.object(agent.time, "time", return_value=1000.0):
+            self.assertIsNone(reach_agent._send_telemetry({"sample": 0}))
+        with mock.patch.object(agent.time, "time", return_value=1030.0):
+            self.assertIsNone(reach_agent._send_telemetry({"sample": 1}))
+        reach_agent.relay.send_telemetry.assert_not_called()
+        with mock.patch.object(agent.time, "time", return_value=1060.0):
+            self.assertTrue(reach_agent._send_telemetry({"sample": 2}))
+        self.assertEqual(len(reach_agent.relay.send_telemetry.call_args[0][0]), 3)
+        self.assertEqual(reach_agent.telemetry_batch, [])
+
+
+class ExtraObjectsTests(unittest.TestCase):
+    def test_parses_object_names(self):
+        config = make_config(
+            REACH_LINK_EXTRA_OBJECTS="temperature_sensor psu, gcode_macro FAN_RPM ,heater_generic chamber"
+        )
+        self.assertEqual(
+            config.extra_objects,
+            ["temperature_sensor psu", "gcode_macro FAN_RPM", "heater_generic chamber"],
+        )
+
+    def test_unset_means_no_extra_objects(self):
+        self.assertEqual(make_config().extra_objects, [])
+
+    def test_rejects_empty_entries(self):
+        for value in ("psu,,fan", "psu,", " , "):
+            with self.subTest(value=value):
+                with self.assertRaises(ValueError):
+                    make_config(REACH_LINK_EXTRA_OBJECTS=value)
+
+    def test_extra_objects_are_queried_and_reported_verbatim(self):
+        client = agent.MoonrakerClient("http://moonraker.local:7125", ["gcode_macro FAN_RPM"])
+        client.sensor_objects = []
+        self.assertIn("&gcode_macro%20FAN_RPM", client.build_status_query())
+        status = {"gcode_macro FAN_RPM": {"rpm": 4200}, "webhooks": {"state": "ready"}}
+        with mock.patch.object(agent.HTTPClient, "get_json", return_value={"result": {"status": status}}):
+            snapshot = client.get_status()
+        self.assertEqual(snapshot["extra"], {"gcode_macro FAN_RPM": {"rpm": 4200}})
+
+
+class ErrorDedupTests(unittest.TestCase):
+    SHUTDOWN = {"type": "klippy_shutdown", "severity": "critical", "message": "MCU 'mcu' shutdown"}
+
+    def test_suppressed_within_window_after_send(self):
+        dedup = agent.ErrorDeduplicator(300)
+        dedup.mark_sent(dedup.filter([self.SHUTDOWN], 1000.0), 1000.0)
+        self.assertEqual(dedup.filter([self.SHUTDOWN], 1100.0), [])
+
+    def test_re_emitted_after_window_as_repeated(self):
+        dedup = agent.ErrorDeduplicator(300)
+        dedup.mark_sent(dedup.filter([self.SHUTDOWN], 1000.0), 1000.0)
+        self.assertEqual(dedup.filter([self.SHUTDOWN], 1300.0), [dict(self.SHUTDOWN, repeated=True)])
+
+    def test_cleared_condition_is_forgotten(self):
+        dedup = agent.ErrorDeduplicator(300)
+        dedup.mark_sent(dedup.filter([self.SHUTDOWN], 1000.0), 1000.0)
+        dedup.filter([], 1010.0)
+        self.assertEqual(dedup.filter([self.SHUTDOWN], 1020.0), [self.SHUTDOWN])
+
+    def test_unsent_error_is_not_suppressed(self):
+        dedup = agent.ErrorDeduplicator(300)
+        dedup.filter([self.SHUTDOWN], 1000.0)
+        self.assertEqual(dedup.filter([self.SHUTDOWN], 1010.0), [self.SHUTDOWN])
+
+    def test_failed_send_retries_the_error_on_the_next_cycle(self):
+        reach_agent = make_agent()
+        reach_agent.relay.build_telemetry_payload.side_effect = (
+            lambda status: {"errors": status.get("errors") or []}
+        )
+        reach_agent.relay.send_telemetry.return_value = False
+        errors = reach_agent.error_dedup.filter([self.SHUTDOWN], time.time())
+        self.assertFalse(reach_agent._send_telemetry({"errors": errors}))
+
+        # The relay is back; the shutdown (now cleared on the printer) still goes out.
+        reach_agent.relay.send_telemetry.return_value = True
+        errors = reach_agent.error_dedup.filter(reach_agent.error_queue.drain(), time.time())
+        self.assertEqual(errors, [self.SHUTDOWN])
+        self.assertTrue(reach_agent._send_telemetry({"errors": errors}))
+        self.assertEqual(reach_agent.error_dedup.filter([self.SHUTDOWN], time.time()), [])
+
+
+class ConnectionPoolTests(unittest.TestCase):
+    def test_pool_settings_parse_and_default_non_zero(self):
+        config = make_config()
+        self.assertGreater(config.pool_idle_secs, 0)
+        self.assertGreater(config.pool_max_idle_per_host, 0)
+        config = make_config(REACH_LINK_POOL_IDLE_SECS="30", REACH_LINK_POOL_MAX_IDLE_PER_HOST="4")
+        self.assertEqual((config.pool_idle_secs, config.pool_max_idle_per_host), (30, 4))
+
+    def test_rejects_invalid_pool_settings(self):
+        for env in ({"REACH_LINK_POOL_IDLE_SECS": "-1"}, {"REACH_LINK_POOL_MAX_IDLE_PER_HOST": "0"}):
+            with self.subTest(env=env):
+                with self.assertRaises(ValueError):
+                    make_config(**env)
+
+    def _opened_via_pool(self, url, environ):
+        pool = mock.Mock()
+        with mock.patch.dict(os.environ, environ, clear=True), \
+                mock.patch.object(agent.HTTPClient, "pool", pool), \
+                mock.patch.object(agent, "urlopen") as urlopen:
+            agent.HTTPClient.open(url, timeout=5)
+        return pool.request.called, urlopen.called
+
+    def test_direct_requests_use_the_pool(self):
+        self.assertEqual(self._opened_via_pool("https://relay.example.com/x", {}), (True, False))
+
+    def test_proxied_requests_bypass_the_pool(self):
+        environ = {"https_proxy": "http://proxy.lan:3128", "no_proxy": "127.0.0.1,localhost"}
+        self.assertEqual(self._opened_via_pool("https://relay.example.com/x", environ), (False, True))
+        # no_proxy hosts still connect directly, through the pool.
+        self.assertEqual(self._opened_via_pool("http://127.0.0.1:7125/x", environ), (True, False))
+
+
+class EmergencyStopTests(unittest.TestCase):
+    def test_mismatched_printer_id_is_rejected_without_stopping(self):
+        reach_agent = make_agent()
+        with mock.patch.object(reach_agent, "proxy_command_to_moonraker") as proxy:
+            result = reach_agent._execute_command(
+                "emergency_stop", {"printer_id": "some-other-printer"}, "req-1"
+            )
+        self.assertEqual(result["errorCode"], "printer_mismatch")
+        proxy.assert_not_called()
+
+    def test_missing_printer_id_is_rejected(self):
+        reach_agent = make_agent()
+        with mock.patch.object(reach_agent, "proxy_command_to_moonraker") as proxy:
+            result = reach_agent._execute_command("emergency_stop", {}, "req-2")
+        self.assertEqual(result["errorCode"], "printer_mismatch")
+        proxy.assert_not_called()
+
+    def test_matching_printer_id_stops_and_reports_klippy_state(self):
+        reach_agent = make_agent()
+        replies = [{"result": "ok"}, {"result": {"klippy_state": "shutdown"}}]
+        with mock.patch.object(reach_agent, "proxy_command_to_moonraker", side_effect=replies) as proxy:
+            result = reach_agent._execute_command(
+                "emergency_stop", {"printer_id": "test-printer"}, "req-3"
+            )
+        self.assertEqual(result, {"result": "ok", "klippyState": "shutdown"})
+        self.assertEqual(proxy.call_args_list[0][0][0], "printer.emergency_stop")
+
+
+class TemperatureWindowTests(unittest.TestCase):
+    def test_min_max_avg_over_several_samples(self):
+        window = agent.TemperatureWindow()
+        for nozzle, bed in ((205.0, 59.5), (211.5, 60.25), (208.0, None), (209.5, 60.0)):
+            window.add({"nozzle": nozzle, "bed": bed})
+        self.assertEqual(window.summary(), {
+            "nozzleMin": 205.0,
+            "nozzleMax": 211.5,
+            "nozzleAvg": 208.5,
+            "bedMin": 59.5,
+            "bedMax": 60.25,
+            "bedAvg": 59.92,
+        })
+
+    def test_empty_window_and_reset(self):
+        window = agent.TemperatureWindow()
+        window.add({"nozzle": 200.0})
+        window.reset()
+        self.assertTrue(all(value is None for value in window.summary().values()))
+
+
+def _ipv6_loopback_available() -> bool:
+    try:
+        with socket.socket(socket.AF_INET6, socket.SOCK_STREAM) as sock:
+            sock.bind(("::1", 0))
+        return True
+    except OSError:
+        return False
+
+
+class HealthIpv6Tests(unittest.TestCase):
+    def test_parses_ipv6_bind(self):
+        self.assertEqual(make_config(REACH_LINK_HEALTH_BIND="::1").health_addr, ("::1", 8080))
+
+    @unittest.skipUnless(_ipv6_loopback_available(), "no IPv6 loopback")
+    def test_binds_ipv6_loopback(self):
+        owner = mock.Mock()
+        owner.health_status.return_value = {"status": "ok"}
+        server = agent.HealthServer(owner, ("::1", 0))
+        self.assertTrue(server.start())
+        try:
+            port = server.httpd.server_address[1]
+            with agent.urlopen(f"http://[::1]:{port}/health", timeout=5) as response:
+                self.assertEqual(json.loads(response.read()), {"status": "ok"})
+        finally:
+            server.stop()
+
+
+class MultiPrinterTests(unittest.TestCase):
+    def test_two_printers_produce_independent_snapshots(self):
+        tmp = tempfile.TemporaryDirectory()
+        self.addCleanup(tmp.cleanup)
+        printers_file = os.path.join(tmp.name, "printers.json")
+        with open(printers_file, "w", encoding="utf-8") as f:
+            json.dump([
+                {"printerId": "printer-a", "token": "token-a", "moonrakerUrl": "http://printer-a.lan:7125"},
+                {"printerId": "printer-b", "token": "token-b", "moonrakerUrl": "http://printer-b.lan:7125"},
+            ], f)
+        fleet = agent.AgentFleet(make_config(REACH_LINK_PRINTERS_FILE=printers_file))
+        printer_a, printer_b = fleet.agents
+
+        nozzle = {"printer-a.lan": 215.0, "printer-b.lan": 180.0}
+
+        def fake_get_json(url, *args, **kwargs):
+            if "/printer/objects/list" in url:
+                return {"result": {"objects": []}}
+            status = {"extruder": {"temperature": nozzle[agent.urlparse(url).hostname]}}
+            return {"result": {"status": status}}
+
+        with mock.patch.object(agent.HTTPClient, "get_json", side_effect=fake_get_json):
+            snapshot_a = printer_a.moonraker.get_status()
+            snapshot_b = printer_b.moonraker.get_status()
+
+        self.assertEqual(snapshot_a["temperatures"]["nozzle"], 215.0)
+        self.assertEqual(snapshot_b["temperatures"]["nozzle"], 180.0)
+        self.assertEqual(
+            [(a.relay.printer_id, a.relay.token) for a in fleet.agents],
+            [("printer-a", "token-a"), ("printer-b", "token-b")],
+        )
+        payload_a = printer_a.relay.build_telemetry_payload(snapshot_a)
+        payload_b = printer_b.relay.build_telemetry_payload(snapshot_b)
+        self.assertEqual((payload_a["printerId"], payload_a["seq"]), ("printer-a", 1))
+        self.assertEqual((payload_b["printerId"], payload_b["seq"]), ("printer-b", 1))
+
+    def _fleet_files(self, tmp, token_b="token-b"):
+        printers_file = os.path.join(tmp, "printers.json")
+        with open(printers_file, "w", encoding="utf-8") as f:
+            json.dump([
+                {"printerId": "printer-a", "token": "token-a",
+                 "stateFile": os.path.join(tmp, "printer-a.json")},
+                {"printerId": "printer-b", "token": token_b,
+                 "stateFile": os.path.join(tmp, "printer-b.json")},
+            ], f)
+        return printers_file
+
+    def test_rotated_token_survives_a_fleet_restart(self):
+        tmp = tempfile.TemporaryDirectory()
+        self.addCleanup(tmp.cleanup)
+        printers_file = self._fleet_files(tmp.name)
+        fleet = agent.AgentFleet(make_config(REACH_LINK_PRINTERS_FILE=printers_file))
+        fleet.agents[1]._apply_rotated_token({"newToken": "rotated-b"})
+
+        restarted = agent.AgentFleet(make_config(REACH_LINK_PRINTERS_FILE=printers_file))
+        self.assertEqual(
+            [(a.config.token, a.relay.token) for a in restarted.agents],
+            [("token-a", "token-a"), ("rotated-b", "rotated-b")],
+        )
+
+    def test_a_new_token_in_the_printers_file_beats_the_saved_one(self):
+        tmp = tempfile.TemporaryDirectory()
+        self.addCleanup(tmp.cleanup)
+        printers_file = self._fleet_files(tmp.name)
+        fleet = agent.AgentFleet(make_config(REACH_LINK_PRINTERS_FILE=printers_file))
+        fleet.agents[1]._apply_rotated_token({"newToken": "rotated-b"})
+
+        self._fleet_files(tmp.name, token_b="re-paired-b")
+        restarted = agent.AgentFleet(make_config(REACH_LINK_PRINTERS_FILE=printers_file))
+        self.assertEqual(restarted.agents[1].relay.token, "re-paired-b")
+
+
+class LayerInfoTests(unittest.TestCase):
+    def test_layer_info_present(self):
+        job = moonraker_snapshot({
+            "print_stats": {
+                "state": "printing",
+                "filename": "benchy.gcode",
+                "info": {"current_layer": 12, "total_layer": 240},
+            },
+        })["job"]
+        self.assertEqual((job["currentLayer"], job["totalLayer"]), (12, 240))
+
+    def test_layer_info_absent(self):
+        for print_stats in (
+            {"state": "printing", "filename": "benchy.gcode"},
+            {"state": "printing", "filename": "benchy.gcode", "info": None},
+            {"state": "printing", "filename": "benchy.gcode", "info": {"total_layer": None}},
+        ):
+            with self.subTest(print_stats=print_stats):
+                job = moonraker_snapshot({"print_stats": print_stats})["job"]
+                self.assertIsNone(job["currentLayer"])
+                self.assertIsNone(job["totalLayer"])
+
+
+class RelayRetryTests(unittest.TestCase):
+    def _send(self, statuses):
+        """send_telemetry against a relay answering with `statuses` in turn (200 once exhausted)."""
+        attempts = []
+
+        def fake_open(req, timeout):
+            attempts.append(req)
+            status = statuses[len(attempts) - 1] if len(attempts) <= len(statuses) else 200
+            if status >= 400:
+                raise agent.HTTPError(req.full_url, status, "Bad Gateway", {}, None)
+            return json_response({"ok": True})
+
+        relay = make_relay()
+        with mock.patch.object(agent.HTTPClient, "open", side_effect=fake_open), \
+                mock.patch.object(agent.time, "sleep") as sleep:
+            ok = relay.send_telemetry({"seq": 1})
+        return ok, attempts, [c.args[0] for c in sleep.call_args_list]
+
+    def test_two_502s_then_success(self):
+        ok, attempts, sleeps = self._send([502, 502])
+        self.assertTrue(ok)
+        self.assertEqual(len(attempts), 3)
+        self.assertEqual(sleeps, [agent._RELAY_RETRY_DELAY_SECS] * 2)
+
+    def test_gives_up_after_configured_retries(self):
+        ok, attempts, _ = self._send([502, 502, 502, 502])
+        self.assertFalse(ok)
+        self.assertEqual(len(attempts), 3)
+
+    def test_4xx_is_not_retried(self):
+        ok, attempts, sleeps = self._send([400])
+        self.assertFalse(ok)
+        self.assertEqual(len(attempts), 1)
+        self.assertEqual(sleeps, [])
+
+
+class RequestSigningTests(unittest.TestCase):
+    BODY = b'{"printerId": "test-printer", "seq": 1}'
+    SIGNATURE = "4d1d71ff123e04843ebfbe7cbf3e39b98cf9894c6ee80a56172fe0422f3bd37c"
+
+    def _capture(self, send):
+        sent = []
+
+        def fake_open(req, timeout):
+            sent.append(req)
+            return json_response({"ok": True})
+
+        with mock.patch.object(agent.HTTPClient, "open", side_effect=fake_open), \
+                mock.patch.object(agent.time, "time", return_value=1700000000.4):
+            send()
+        return sent[0]
+
+    def test_known_payload_and_secret(self):
+        self.assertEqual(agent.sign_request("shh", "1700000000", self.BODY), self.SIGNATURE)
+
+    def test_json_post_is_signed_over_the_sent_bytes(self):
+        relay = make_relay(REACH_LINK_HMAC_SECRET="shh")
+        req = self._capture(
+            lambda: relay.send_telemetry({"printerId": "test-printer", "seq": 1})
+        )
+        self.assertEqual(req.data, self.BODY)
+        self.assertEqual(req.get_header("X-reach-timestamp"), "1700000000")
+        self.assertEqual(req.get_header("X-reach-signature"), self.SIGNATURE)
+
+    def test_webcam_snapshot_is_signed(self):
+        jpeg = b"\xff\xd8\xff\xe0fake-jpeg\xff\xd9"
+        relay = make_relay(REACH_LINK_HMAC_SECRET="shh")
+        req = self._capture(lambda: relay.send_webcam_snapshot(jpeg))
+        self.assertEqual(
+            req.get_header("X-reach-signature"), agent.sign_request("shh", "1700000000", jpeg)
+        )
+
+    def test_unsigned_without_a_secret(self):
+        req = self._capture(lambda: make_relay().send_webcam_snapshot(b"jpeg"))
+        self.assertIsNone(req.get_header("X-reach-signature"))
+
+
+class MaintenanceModeTests(unittest.TestCase):
+    def test_toggle_is_reflected_in_the_telemetry_payload(self):
+        reach_agent = make_agent()
+        reach_agent.relay.build_telemetry_payload.side_effect = make_relay().build_telemetry_payload
+        reach_agent.moonraker.get_status.side_effect = lambda: {
+            "errors": [{"severity": "critical", "code": "KLIPPY_SHUTDOWN", "message": "MCU lost"}],
+        }
+        self.assertTrue(reach_agent.set_maintenance({})["maintenance"])
+        run_loop(reach_agent)
+        payload = reach_agent.relay.send_telemetry.call_args[0][0]
+        self.assertTrue(payload["maintenance"])
+        self.assertEqual(payload["errors"], [])
+
+        self.assertFalse(reach_agent.set_maintenance({})["maintenance"])
+        self.assertFalse(reach_agent.in_maintenance())
+
+    def test_window_expires(self):
+        reach_agent = make_agent()
+        reach_agent.set_maintenance({"enabled": True, "duration": 60})
+        self.assertTrue(reach_agent.in_maintenance(time.time() + 30))
+        self.assertFalse(reach_agent.in_maintenance(time.time() + 61))
+
+
+class HealthControlAuthTests(unittest.TestCase):
+    def setUp(self):
+        self.owner = mock.Mock()
+        self.owner.control_tokens.return_value = ["test-token"]
+        self.owner.set_maintenance.return_value = {"maintenance": True}
+        self.server = agent.HealthServer(self.owner, ("127.0.0.1", 0))
+        self.assertTrue(self.server.start())
+        self.addCleanup(self.server.stop)
+
+    def _post(self, headers=None) -> int:
+        port = self.server.httpd.server_address[1]
+        req = agent.Request(
+            f"http://127.0.0.1:{port}/maintenance", data=b"{}", headers=headers or {}, method="POST"
+        )
+        try:
+            with agent.urlopen(req, timeout=5) as response:
+                return response.status
+        except agent.HTTPError as e:
+            e.close()
+            return e.code
+
+    def test_loopback_client_needs_no_token(self):
+        self.assertEqual(self._post(), 200)
+        self.owner.set_maintenance.assert_called_once_with({})
+
+    def test_remote_client_needs_the_agent_token(self):
+        with mock.patch.object(agent, "is_loopback_address", return_value=False):
+            self.assertEqual(self._post(), 403)
+            self.assertEqual(self._post({"Authorization": "Bearer wrong-token"}), 403)
+            self.owner.set_maintenance.assert_not_called()
+            self.assertEqual(self._post({"Authorization": "Bearer test-token"}), 200)
+
+    def test_loopback_addresses(self):
+        for host, expected in (
+            ("127.0.0.1", True), ("127.8.9.1", True), ("::1", True), ("::ffff:127.0.0.1", True),
+            ("192.168.1.20", False), ("::ffff:10.0.0.5", False), ("fe80::1%eth0", False),
+        ):
+            with self.subTest(host=host):
+                self.assertEqual(agent.is_loopback_address(host), expected)
+
+
+class ObjectRediscoveryTests(unittest.TestCase):
+    def test_changed_object_list_updates_the_query_after_a_restart(self):
+        object_lists = [
+            ["extruder", "heater_bed", "temperature_sensor chamber"],
+            ["extruder", "heater_bed", "temperature_sensor chamber", "temperature_fan exhaust"],
+        ]
+        # The restart is seen on the third query; the fourth re-discovers.
+        klippy_states = ["ready", "startup", "ready", "ready"]
+        queries = []
+
+        def fake_get_json(url, *args, **kwargs):
+            if "/printer/objects/list" in url:
+                return {"result": {"objects": object_lists.pop(0)}}
+            queries.append(url)
+            return {"result": {"status": {"webhooks": {"state": klippy_states[len(queries) - 1]}}}}
+
+        client = agent.MoonrakerClient("http://moonraker.local:7125")
+        with mock.patch.object(agent.HTTPClient, "get_json", side_effect=fake_get_json):
+            for _ in klippy_states:
+                client.get_status()
+
+        self.assertEqual(object_lists, [])
+        self.assertIn("&temperature_sensor%20chamber=temperature,target", queries[0])
+        self.assertNotIn("temperature_fan", queries[1])
+        self.assertNotIn("temperature_fan", queries[2])
+        self.assertIn("&temperature_fan%20exhaust=temperature,target", queries[3])
+        self.assertEqual(client.sensor_objects, ["temperature_fan exhaust", "temperature_sensor chamber"])
+
+
+class SinceLastSuccessTests(unittest.TestCase):
+    def test_field_from_known_last_success(self):
+        self.assertEqual(
+            agent.format_since_last_success(1000.0, 1240.7), "since_last_success_secs=240"
+        )
+        self.assertEqual(agent.format_since_last_success(None, 1240.7), "since_last_success_secs=never")
+        # A clock step backwards never produces a negative age.
+        self.assertEqual(agent.format_since_last_success(1300.0, 1240.7), "since_last_success_secs=0")
+
+    def test_failed_send_logs_the_field(self):
+        relay = make_relay()
+        with mock.patch.object(agent.HTTPClient, "post_json", return_value=None), \
+                mock.patch.object(agent.time, "time", return_value=1240.0), \
+                self.assertLogs(agent.logger, "WARNING") as logs:
+            relay.send_telemetry({"seq": 1}, last_ok=1000.0)
+        self.assertIn("since_last_success_secs=240", logs.output[0])
+
+
+class CompressedResponseTests(unittest.TestCase):
+    STATUS = {
+        "extruder": {"temperature": 205.5, "target": 205.0},
+        "print_stats": {"state": "printing", "filename": "benchy.gcode"},
+        "webhooks": {"state": "ready"},
+    }
+
+    def setUp(self):
+        patcher = mock.patch.multiple(agent.HTTPClient, ssl_context=None, pool=None)
+        patcher.start()
+        self.addCleanup(patcher.stop)
+
+    def _snapshot(self, encoding, compress):
+        def respond(request):
+            if request["path"].startswith("/printer/objects/list"):
+                return json_reply({"result": {"objects": ["extruder"]}})
+            body = json.dumps({"result": {"status": self.STATUS}}).encode("utf-8")
+            return 200, {"Content-Type": "application/json", "Content-Encoding": encoding}, compress(body)
+
+        with RecordingServer(respond) as server:
+            snapshot = agent.MoonrakerClient(server.url).get_status()
+        for request in server.requests:
+            self.assertEqual(request["headers"]["Accept-Encoding"], "gzip, deflate")
+        return snapshot
+
+    def test_gzip_body_is_decoded(self):
+        snapshot = self._snapshot("gzip", agent.gzip.compress)
+        self.assertEqual(snapshot["temperatures"]["nozzle"], 205.5)
+        self.assertEqual(snapshot["job"]["filename"], "benchy.gcode")
+
+    def test_deflate_body_is_decoded(self):
+        raw_deflate = lambda body: agent.zlib.compress(body)[2:-4]
+        for compress in (agent.zlib.compress, raw_deflate):
+            with self.subTest(compress=compress):
+                self.assertEqual(self._snapshot("deflate", compress)["temperatures"]["nozzle"], 205.5)
+
+
+class HeaterPowerTests(unittest.TestCase):
+    def test_power_is_parsed_alongside_temperature(self):
+        temperatures = moonraker_snapshot({
+            "extruder": {"temperature": 238.2, "target": 250.0, "power": 1.0},
+            "heater_bed": {"temperature": 60.0, "target": 60.0, "power": 0.27},
+        })["temperatures"]
+        self.assertEqual((temperatures["nozzle"], temperatures["nozzlePower"]), (238.2, 1.0))
+        self.assertEqual((temperatures["bed"], temperatures["bedPower"]), (60.0, 0.27))
+
+    def test_missing_power_is_none(self):
+        temperatures = moonraker_snapshot({"extruder": {"temperature": 24.0}})["temperatures"]
+        self.assertEqual(temperatures["nozzle"], 24.0)
+        self.assertIsNone(temperatures["nozzlePower"])
+        self.assertIsNone(temperatures["bedPower"])
+
+
+class JobQueueTests(unittest.TestCase):
+    def test_parses_queue_with_two_pending_jobs(self):
+        response = {
+            "result": {
+                "queued_jobs": [
+                    {"filename": "part_a.gcode", "job_id": "0000000066D99C90", "time_added": 1636151050.7},
+                    {"filename": "sub/part_b.gcode", "job_id": "0000000066D991F0", "time_added": 1636151060.2},
+                ],
+                "queue_state": "paused",
+            }
+        }
+        self.assertEqual(agent.parse_job_queue(response), {
+            "state": "paused",
+            "jobs": [
+                {"filename": "part_a.gcode", "jobId": "0000000066D99C90", "timeAdded": 1636151050.7},
+                {"filename": "sub/part_b.gcode", "jobId": "0000000066D991F0", "timeAdded": 1636151060.2},
+            ],
+        })
+
+    def test_job_queue_disabled(self):
+        client = agent.MoonrakerClient("http://moonraker.local:7125")
+        with mock.patch.object(agent.HTTPClient, "get_json", return_value=None):
+            self.assertIsNone(client.get_job_queue())
+
+
+class LogTailTests(unittest.TestCase):
+    def test_oversized_line_is_truncated_from_the_front_with_marker(self):
+        huge = "Traceback: " + "x" * 100_000 + " END"
+        lines = agent.clamp_log_tail(["older line", huge], 8192)
+        self.assertEqual(len(lines), 1)
+        self.assertTrue(lines[0].startswith(agent._LOG_TAIL_TRUNCATED_MARKER))
+        self.assertTrue(lines[0].endswith("x END"))
+        self.assertEqual(len(lines[0].encode("utf-8")), 8192)
+
+    def test_small_tail_is_untouched(self):
+        self.assertEqual(agent.clamp_log_tail(["a", "b"], 8192), ["a", "b"])
+
+    def test_each_printer_thread_reads_only_its_own_lines(self):
+        tail = agent.LogTail(50)
+        tail.setFormatter(logging.Formatter("%(message)s"))
+        log = logging.getLogger("reach-link-test-log-tail")
+        log.propagate = False
+        log.addHandler(tail)
+        self.addCleanup(log.removeHandler, tail)
+
+        def log_from(printer_id):
+            thread = threading.Thread(
+                target=log.warning, args=(f"hello from {printer_id}",), name=f"printer-{printer_id}"
+            )
+            thread.start()
+            thread.join()
+
+        log_from("a")
+        log_from("b")
+        log.warning("fleet startup")
+
+        self.assertEqual(tail.since(0, "printer-a")[0], ["hello from a"])
+        self.assertEqual(tail.since(0, "printer-b")[0], ["hello from b"])
+        self.assertEqual(tail.since(0)[0], ["hello from a", "hello from b", "fleet startup"])
+        self.assertEqual(tail.since(tail.since(0)[1], "printer-a"), ([], 3))
+
+
+class AgentCommitTests(unittest.TestCase):
+    def setUp(self):
+        patcher = mock.patch.object(agent, "_agent_commit", None)
+        patcher.start()
+        self.addCleanup(patcher.stop)
+
+    def test_stamped_build_commit_wins(self):
+        with mock.patch.object(agent, "BUILD_COMMIT", "0123456789ab"), \
+                mock.patch.object(agent.subprocess, "run") as run:
+            self.assertEqual(agent.agent_commit(), "0123456789ab")
+        run.assert_not_called()
+
+    def test_untracked_copy_inside_another_repo_is_unknown(self):
+        untracked = agent.subprocess.CompletedProcess([], 1, "", "error: pathspec did not match")
+        with mock.patch.object(agent.subprocess, "run", return_value=untracked) as run:
+            self.assertEqual(agent.agent_commit(), "unknown")
+        self.assertEqual(run.call_count, 1)
+        self.assertEqual(run.call_args[0][0][:3], ["git", "ls-files", "--error-unmatch"])
+
+    def test_tracked_checkout_uses_head(self):
+        results = [
+            agent.subprocess.CompletedProcess([], 0, "src/reach-link-agent.py\n", ""),
+            agent.subprocess.CompletedProcess([], 0, "fedcba987654\n", ""),
+        ]
+        with mock.patch.object(agent.subprocess, "run", side_effect=results):
+            self.assertEqual(agent.agent_commit(), "fedcba987654")
+
+    def test_register_payload_carries_a_non_empty_commit(self):
+        relay = make_relay()
+        with mock.patch.object(agent.HTTPClient, "post_json", return_value={"ok": True}) as post_json:
+            relay.register_heartbeat(0)
+        commit = json.loads(json.dumps(post_json.call_args[0][1]))["commit"]
+        self.assertIsInstance(commit, str)
+        self.assertTrue(commit)
+
+
+class ShutdownGcodeTests(unittest.TestCase):
+    def test_hook_fires_in_the_shutdown_path(self):
+        reach_agent = make_agent(REACH_LINK_SHUTDOWN_GCODE="TURN_OFF_HEATERS")
+        events = []
+        reach_agent.moonraker.execute_gcode.side_effect = (
+            lambda script, timeout: events.append(("gcode", script, timeout)) or True
+        )
+        run_loop(reach_agent, events=events)
+        self.assertEqual(
+            events, ["sleep", ("gcode", "TURN_OFF_HEATERS", agent._SHUTDOWN_GCODE_TIMEOUT_SECS)]
+        )
+
+    def test_no_hook_configured(self):
+        reach_agent = make_agent()
+        run_loop(reach_agent)
+        reach_agent.moonraker.execute_gcode.assert_not_called()
+
+
+class JobEndEventTests(unittest.TestCase):
+    def test_events_on_leaving_an_active_print(self):
+        for previous, state, expected in (
+            ("printing", "complete", "print_complete"),
+            ("paused", "cancelled", "print_cancelled"),
+            ("printing", "error", "print_failed"),
+        ):
+            with self.subTest(previous=previous, state=state):
+                event = agent.job_end_event(previous, {"state": state, "filename": "benchy.gcode"})
+                self.assertEqual(event, {"type": expected, "filename": "benchy.gcode"})
+
+    def test_no_event_without_an_edge(self):
+        for previous, job in (
+            (None, {"state": "complete"}),
+            ("complete", {"state": "complete"}),
+            ("idle", {"state": "error"}),
+            ("printing", {"state": "printing"}),
+            ("printing", {"state": "paused"}),
+            ("printing", None),
+        ):
+            with self.subTest(previous=previous, job=job):
+                self.assertIsNone(agent.job_end_event(previous, job))
+
+    def test_event_is_sent_once_with_the_finishing_sample(self):
+        reach_agent = make_agent()
+        reach_agent.relay.build_telemetry_payload.side_effect = make_relay().build_telemetry_payload
+        reach_agent.moonraker.get_status.side_effect = lambda: {
+            "job": {"state": "complete", "filename": "benchy.gcode"},
+        }
+        reach_agent.last_job_state = "printing"
+        run_loop(reach_agent)
+        payload = reach_agent.relay.send_telemetry.call_args[0][0]
+        self.assertEqual(payload["jobEvent"], {"type": "print_complete", "filename": "benchy.gcode"})
+        self.assertEqual(reach_agent.last_job_state, "complete")
+
+
+class LogLevelTests(unittest.TestCase):
+    def setUp(self):
+        root = logging.getLogger()
+        self.handler = logging.NullHandler()
+        self.handler.setLevel(logging.INFO)
+        root.addHandler(self.handler)
+        self.addCleanup(root.removeHandler, self.handler)
+        self.addCleanup(root.setLevel, root.level)
+
+    def test_toggle_between_info_and_debug(self):
+        root = logging.getLogger()
+        self.assertEqual(agent.set_log_level({"level": "DEBUG"}), {"level": "debug"})
+        self.assertEqual((root.level, self.handler.level), (logging.DEBUG, logging.DEBUG))
+        self.assertEqual(agent.set_log_level({"level": "info"}), {"level": "info"})
+        self.assertEqual((root.level, self.handler.level), (logging.INFO, logging.INFO))
+
+    def test_rejects_unknown_level(self):
+        for request in ({"level": "verbose"}, {}):
+            with self.subTest(request=request):
+                with self.assertRaises(ValueError):
+                    agent.set_log_level(request)
+
+    def test_post_log_level_route(self):
+        server = agent.HealthServer(mock.Mock(), ("127.0.0.1", 0))
+        self.assertTrue(server.start())
+        self.addCleanup(server.stop)
+        port = server.httpd.server_address[1]
+        req = agent.Request(
+            f"http://127.0.0.1:{port}/log-level", data=b'{"level": "debug"}', method="POST"
+        )
+        with agent.urlopen(req, timeout=5) as response:
+            self.assertEqual(json.loads(response.read()), {"level": "debug"})
+        self.assertEqual(logging.getLogger().level, logging.DEBUG)
+
+
+class TimestampFormatTests(unittest.TestCase):
+    NOW = 1700000000.123
+
+    def test_both_formats(self):
+        self.assertEqual(agent.format_timestamp(self.NOW, "unix_ms"), 1700000000123)
+        self.assertEqual(agent.format_timestamp(self.NOW, "rfc3339"), "2023-11-14T22:13:20.123Z")
+
+    def test_payload_uses_the_configured_format(self):
+        for fmt, expected in (("unix_ms", 1700000000123), ("RFC3339", "2023-11-14T22:13:20.123Z")):
+            with self.subTest(fmt=fmt):
+                relay = make_relay(REACH_LINK_TIMESTAMP_FORMAT=fmt)
+                with mock.patch.object(agent.time, "time", return_value=self.NOW):
+                    self.assertEqual(relay.build_telemetry_payload({})["timestamp"], expected)
+
+    def test_rejects_unknown_format(self):
+        with self.assertRaises(ValueError):
+            make_config(REACH_LINK_TIMESTAMP_FORMAT="iso")
+
+
+class JobTotalsTests(unittest.TestCase):
+    def test_parses_history_totals(self):
+        response = {
+            "result": {
+                "job_totals": {
+                    "total_jobs": 3,
+                    "total_time": 11748.077333278954,
+                    "total_print_time": 11348.794790096988,
+                    "total_filament_used": 11615.718840001999,
+                    "longest_job": 11665.191012736992,
+                    "longest_print": 11348.794790096988,
+                }
+            }
+        }
+        self.assertEqual(agent.parse_job_totals(response), {
+            "totalJobs": 3,
+            "totalPrintTime": 11348,
+            "totalFilamentUsed": 11615.718840001999,
+            "longestJob": 11665,
+        })
+
+    def test_empty_history(self):
+        self.assertEqual(agent.parse_job_totals({"result": {"job_totals": None}}), {
+            "totalJobs": 0, "totalPrintTime": 0, "totalFilamentUsed": None, "longestJob": 0,
+        })
+
+
+class HealthOptionalTests(unittest.TestCase):
+    def test_bind_failure_is_fatal_by_default(self):
+        self.assertFalse(make_config().health_optional)
+        self.assertFalse(make_config(REACH_LINK_HEALTH_PORT="9090").health_optional)
+        self.assertFalse(make_config(REACH_LINK_HEALTH_OPTIONAL="0").health_optional)
+
+    def test_opt_out(self):
+        self.assertTrue(make_config(REACH_LINK_HEALTH_OPTIONAL="1").health_optional)
+
+    def _start_on_taken_port(self, required):
+        taken = socket.socket(socket.AF_INET, socket.SOCK_STREAM)
+        self.addCleanup(taken.close)
+        taken.bind(("127.0.0.1", 0))
+        taken.listen(1)
+        server = agent.HealthServer(mock.Mock(), taken.getsockname())
+        self.addCleanup(server.stop)
+        return server.start(required=required)
+
+    def test_taken_port_is_fatal_when_required(self):
+        with self.assertRaises(agent.HealthBindError):
+            self._start_on_taken_port(required=True)
+
+    def test_taken_port_is_logged_when_optional(self):
+        self.assertFalse(self._start_on_taken_port(required=False))
+
+
+class TagsTests(unittest.TestCase):
+    def test_parses_key_value_pairs(self):
+        self.assertEqual(
+            agent.Config._parse_tags(" site=lab, owner = ops ,,shelf="),
+            {"site": "lab", "owner": "ops", "shelf": ""},
+        )
+        self.assertEqual(make_config().tags, {})
+
+    def test_rejects_malformed_entry(self):
+        for value in ("site=lab,owner", "=ops", "site=lab, =x"):
+            with self.subTest(value=value):
+                with self.assertRaisesRegex(ValueError, "REACH_LINK_TAGS"):
+                    make_config(REACH_LINK_TAGS=value)
+
+
+class RequestDurationTests(unittest.TestCase):
+    def _post(self, open_side_effect):
+        relay = make_relay()
+        with mock.patch.object(agent.HTTPClient, "open", side_effect=open_side_effect), \
+                mock.patch.object(agent.time, "monotonic", side_effect=[100.0, 100.25]), \
+                self.assertLogs(agent.logger, "DEBUG") as logs:
+            relay.register_heartbeat(0)
+        return [line for line in logs.output if "HTTP POST" in line and " -> " in line]
+
+    def test_duration_and_status_are_logged(self):
+        (line,) = self._post(lambda req, timeout: json_response({"ok": True}))
+        self.assertIn("/api/reach-link/register -> 200 in 250ms", line)
+
+    def test_failed_request_logs_its_duration(self):
+        error = agent.HTTPError("https://relay.example.com", 400, "Bad Request", {}, None)
+        (line,) = self._post(error)
+        self.assertIn("-> 400 in 250ms", line)
+
+    def test_duration_is_positive(self):
+        with mock.patch.object(agent.logger, "debug") as debug:
+            agent.HTTPClient.log_duration("POST", "https://relay.example.com/x", 200, time.monotonic() - 0.01)
+        elapsed = float(debug.call_args[0][0].rsplit(" in ", 1)[1].rstrip("ms"))
+        self.assertGreater(elapsed, 0)
+
+
+class MoonrakerUrlTests(unittest.TestCase):
+    def test_missing_scheme_is_rejected(self):
+        for url in ("printer.local:7125", "127.0.0.1:7125", "//printer.local:7125"):
+            with self.subTest(url=url):
+                with self.assertRaisesRegex(ValueError, "REACH_LINK_MOONRAKER_URL"):
+                    make_config(REACH_LINK_MOONRAKER_URL=url)
+
+    def test_misspelled_scheme_is_rejected(self):
+        for url in ("htpp://printer.local:7125", "htp://127.0.0.1", "ws://printer.local:7125"):
+            with self.subTest(url=url):
+                with self.assertRaisesRegex(ValueError, "REACH_LINK_MOONRAKER_URL"):
+                    make_config(REACH_LINK_MOONRAKER_URL=url)
+
+    def test_valid_urls_pass(self):
+        for url in ("http://127.0.0.1:7125", "https://printer.local", "http://[::1]:7125"):
+            with self.subTest(url=url):
+                self.assertEqual(make_config(REACH_LINK_MOONRAKER_URL=url).moonraker_url, url)
+
+
+class TokenRotationTests(unittest.TestCase):
+    def test_register_rotates_the_token_for_the_next_request(self):
+        tmp = tempfile.TemporaryDirectory()
+        self.addCleanup(tmp.cleanup)
+        state_file = os.path.join(tmp.name, "state.json")
+        reach_agent = make_agent(REACH_LINK_STATE_FILE=state_file)
+        config = reach_agent.config
+        reach_agent.relay = agent.RelayClient(config.relay_url, config.token, config.printer_id, config)
+        reach_agent.moonraker.get_status.return_value = {"errors": []}
+        reach_agent.moonraker.get_job_queue.return_value = None
+
+        sent = []
+
+        def fake_open(req, timeout):
+            sent.append((agent.urlparse(req.full_url).path, req.get_header("Authorization")))
+            if req.full_url.endswith("/register"):
+                return json_response({"ok": True, "nextCheckIn": 30, "newToken": "rotated-token"})
+            return json_response({"ok": True})
+
+        with mock.patch.object(agent.HTTPClient, "open", side_effect=fake_open), \
+                mock.patch.object(agent.Config, "_env_file_path", return_value=os.path.join(tmp.name, ".env")), \
+                self.assertLogs(agent.logger, "INFO") as logs:
+            run_loop(reach_agent)
+
+        self.assertEqual(sent[:2], [
+            ("/api/reach-link/register", "Bearer test-token"),
+            ("/api/reach-link/printer-data", "Bearer rotated-token"),
+        ])
+        with open(state_file, encoding="utf-8") as f:
+            self.assertEqual(json.load(f)["reachLinkToken"], "rotated-token")
+        self.assertTrue(any("rotated the reach-link token" in line for line in logs.output))
+        self.assertFalse(any("rotated-token" in line for line in logs.output))
+
+        # A restart with REACH_LINK_TOKEN still exported as the old token.
+        self.assertEqual(make_config(REACH_LINK_STATE_FILE=state_file).token, "rotated-token")
+        # Exporting a different token afterwards takes precedence again.
+        self.assertEqual(
+            make_config(REACH_LINK_STATE_FILE=state_file, REACH_LINK_TOKEN="fresh-token").token,
+            "fresh-token",
+        )
+
+
+class TelemetryPacerTests(unittest.TestCase):
+    def test_slow_sends_grow_the_effective_interval(self):
+        reach_agent = make_agent(REACH_LINK_TELEMETRY_INTERVAL="10")
+        reach_agent.moonraker.get_status.return_value = {"errors": []}
+        reach_agent.moonraker.get_job_queue.return_value = None
+        clock = [1000.0]
+
+        def slow_send(payload, last_ok=None):
+            clock[0] += 25  # each send takes 25s against a 10s interval
+            return True
+
+        reach_agent.relay.send_telemetry.side_effect = slow_send
+        with mock.patch.object(agent.time, "time", side_effect=lambda: clock[0]), \
+                self.assertLogs(agent.logger, "WARNING") as logs:
+            run_loop(reach_agent, passes=agent.TelemetryPacer.SLOW_CYCLES)
+
+        self.assertEqual(reach_agent.relay.send_telemetry.call_count, agent.TelemetryPacer.SLOW_CYCLES)
+        self.assertEqual(reach_agent.telemetry_pacer.interval(10), 50)
+        self.assertTrue(any("lagging 15.0s" in line for line in logs.output))
+
+    def test_interval_never_drops_below_configured_and_recovers(self):
+        pacer = agent.TelemetryPacer()
+        for _ in range(pacer.SLOW_CYCLES):
+            pacer.record(12.0, 10, 3600)
+        self.assertEqual(pacer.interval(10), 24)
+        self.assertEqual(pacer.interval(30), 30)
+        pacer.record(4.0, 10, 3600)
+        self.assertEqual(pacer.interval(10), 10)
+
+    def test_widening_is_capped_at_max_interval(self):
+        pacer = agent.TelemetryPacer()
+        for _ in range(pacer.SLOW_CYCLES):
+            pacer.record(500.0, 10, 120)
+        self.assertEqual(pacer.interval(10), 120)
+
+
+class MakeModelTests(unittest.TestCase):
+    HINT = {"make": "Creality", "model": "K1C"}
+
+    def test_explicit_config_wins_over_detected(self):
+        reach_agent = make_agent(REACH_LINK_PRINTER_MAKE="Voron", REACH_LINK_PRINTER_MODEL="2.4")
+        reach_agent.moonraker.get_machine_hint.return_value = self.HINT
+        reach_agent._detect_make_model()
+        self.assertEqual((reach_agent.config.printer_make, reach_agent.config.printer_model), ("Voron", "2.4"))
+        reach_agent.moonraker.get_machine_hint.assert_not_called()
+
+    def test_detection_fills_only_unset_values(self):
+        reach_agent = make_agent(REACH_LINK_PRINTER_MAKE="Voron")
+        reach_agent.moonraker.get_machine_hint.return_value = self.HINT
+        reach_agent._detect_make_model()
+        self.assertEqual((reach_agent.config.printer_make, reach_agent.config.printer_model), ("Voron", "K1C"))
+
+    def test_unset_and_undetectable_is_none_in_the_payload(self):
+        relay = make_relay()
+        with mock.patch.object(agent.HTTPClient, "post_json", return_value={"ok": True}) as post_json:
+            relay.register_heartbeat(0)
+        payload = post_json.call_args[0][1]
+        self.assertIsNone(payload["make"])
+        self.assertIsNone(payload["model"])
+
+
+class CriticalErrorFlushTests(unittest.TestCase):
+    def _run(self, error):
+        reach_agent = make_agent(REACH_LINK_TELEMETRY_INTERVAL="60")
+        reach_agent.relay.build_telemetry_payload.side_effect = make_relay().build_telemetry_payload
+        reach_agent.moonraker.get_job_queue.return_value = None
+        reach_agent.moonraker.get_errors.side_effect = [[], [error]]
+        reach_agent.moonraker.get_status.side_effect = lambda: {"errors": []}
+        clock = [1000.0]
+
+        def send(payload, last_ok=None):
+            clock[0] += agent._ERROR_POLL_SECS + 1
+            return True
+
+        reach_agent.relay.send_telemetry.side_effect = send
+        with mock.patch.object(agent.time, "time", side_effect=lambda: clock[0]):
+            run_loop(reach_agent, passes=2)
+        return [c[0][0] for c in reach_agent.relay.send_telemetry.call_args_list]
+
+    def test_critical_error_is_sent_before_the_interval(self):
+        critical = {"type": "klippy_shutdown", "severity": "critical", "message": "MCU 'mcu' shutdown"}
+        first, second = self._run(critical)
+        self.assertEqual(first["errors"], [])
+        self.assertEqual([e["message"] for e in second["errors"]], [critical["message"]])
+
+    def test_warning_waits_for_the_regular_cycle(self):
+        warning = {"type": "print_error", "severity": "warning", "message": "Filament runout"}
+        (only,) = self._run(warning)
+        self.assertEqual(only["errors"], [])
+
+
+class ShutdownCoordinatorTests(unittest.TestCase):
+    def test_all_subscribers_receive_the_signal(self):
+        coordinator = agent.ShutdownCoordinator(5)
+        woken = []
+
+        def subscriber(name):
+            if coordinator.subscribe().wait(5):
+                woken.append(name)
+
+        threads = [threading.Thread(target=subscriber, args=(n,)) for n in ("loop", "watchdog", "stream")]
+        for thread in threads:
+            thread.start()
+        coordinator.trigger()
+        for thread in threads:
+            thread.join(5)
+        self.assertEqual(sorted(woken), ["loop", "stream", "watchdog"])
+
+    def test_steps_run_in_order_and_a_failing_one_does_not_stop_the_rest(self):
+        coordinator = agent.ShutdownCoordinator(5)
+        ran = []
+        coordinator.register("flush", lambda: ran.append("flush"))
+        coordinator.register("broken", lambda: 1 / 0)
+        coordinator.register("close", lambda: ran.append("close"))
+        self.assertTrue(coordinator.finish())
+        self.assertEqual(ran, ["flush", "close"])
+        self.assertTrue(coordinator.subscribe().is_set())
+
+    def test_stuck_step_is_abandoned_at_the_deadline(self):
+        coordinator = agent.ShutdownCoordinator(0.1)
+        release = threading.Event()
+        self.addCleanup(release.set)
+        ran = []
+        coordinator.register("stuck", release.wait)
+        coordinator.register("after", lambda: ran.append("after"))
+        self.assertFalse(coordinator.finish())
+        self.assertEqual(ran, [])
+
+
+class PrinterLimitsTests(unittest.TestCase):
+    def test_parsed_settings(self):
+        status = {
+            "configfile": {
+                "settings": {"printer": {"kinematics": "corexy", "max_velocity": 500.0, "max_accel": 20000.0}},
+                "config": {"printer": {"kinematics": "cartesian"}},
+            },
+            "toolhead": {"max_velocity": 300.0, "max_accel": 5000.0},
+        }
+        self.assertEqual(
+            agent.parse_printer_limits(status),
+            {"kinematics": "corexy", "maxVelocity": 500.0, "maxAccel": 20000.0},
+        )
+
+    def test_raw_config_strings_with_toolhead_fallback(self):
+        status = {
+            "configfile": {"config": {"printer": {"kinematics": " delta ", "max_velocity": "300"}}},
+            "toolhead": {"max_velocity": 250.0, "max_accel": 3000.0},
+        }
+        self.assertEqual(
+            agent.parse_printer_limits(status),
+            {"kinematics": "delta", "maxVelocity": 300.0, "maxAccel": 3000.0},
+        )
+
+    def test_missing_objects(self):
+        self.assertEqual(
+            agent.parse_printer_limits({}),
+            {"kinematics": None, "maxVelocity": None, "maxAccel": None},
+        )
+
+
+class WatchdogTests(unittest.TestCase):
+    def test_limit_is_multiplier_times_interval_with_a_floor(self):
+        self.assertIsNone(agent.loop_stall_secs(1100.0, 1000.0, 30, 3))
+        self.assertEqual(agent.loop_stall_secs(1121.0, 1000.0, 30, 3), 121.0)
+        self.assertIsNone(agent.loop_stall_secs(1250.0, 1000.0, 100, 3))
+        self.assertEqual(agent.loop_stall_secs(1301.0, 1000.0, 100, 3), 301.0)
+
+    def _check_once(self, reach_agent, now):
+        """One watchdog check at `now`; returns the exit_agent calls it made."""
+        reach_agent.shutdown_event = mock.Mock()
+        reach_agent.shutdown_event.wait.side_effect = [False, True]
+        with mock.patch.object(agent, "exit_agent") as exit_agent, \
+                mock.patch.object(agent.time, "time", return_value=now):
+            reach_agent._watchdog()
+        return exit_agent.call_args_list
+
+    def test_stale_progress_timestamp_exits(self):
+        reach_agent = make_agent(REACH_LINK_WATCHDOG_MULTIPLIER="3")
+        reach_agent.last_progress = 1000.0
+        (call,) = self._check_once(reach_agent, 1000.0 + agent._WATCHDOG_MIN_SECS + 1)
+        self.assertEqual(call[0][0], agent.ExitReason.WATCHDOG)
+        self.assertTrue(call[1]["hard"])
+
+    def test_fresh_progress_timestamp_does_not_exit(self):
+        reach_agent = make_agent(REACH_LINK_WATCHDOG_MULTIPLIER="3")
+        reach_agent.last_progress = 1000.0
+        self.assertEqual(self._check_once(reach_agent, 1060.0), [])
+
+    def test_slow_pass_during_a_relay_outage_keeps_progress_fresh(self):
+        # Worst-case timings from the relay client's timeouts and retries.
+        reach_agent = make_agent()
+        reach_agent.moonraker.get_status.return_value = {"errors": []}
+        reach_agent.moonraker.get_job_queue.return_value = None
+        clock = [1000.0]
+        stale = []  # how old last_progress is as each blocking call returns
+
+        def blocking(secs, result):
+            def call(*args, **kwargs):
+                clock[0] += secs
+                stale.append(clock[0] - reach_agent.last_progress)
+                return result
+            return call
+
+        reach_agent.relay.register_heartbeat.side_effect = blocking(32, None)
+        reach_agent.relay.send_telemetry.side_effect = blocking(32, False)
+        reach_agent.relay.pull_command.side_effect = blocking(93, None)
+        with mock.patch.object(agent.time, "time", side_effect=lambda: clock[0]):
+            run_loop(reach_agent)
+
+        # The pass as a whole outlasts the floor; no single step comes close.
+        self.assertGreater(clock[0] - 1000.0, agent._WATCHDOG_MIN_SECS)
+        self.assertEqual(stale, [32, 32, 93])
+        self.assertEqual(reach_agent.last_progress, clock[0])
+
+
+class QueryCommandTests(unittest.TestCase):
+    def test_query_round_trips_objects_through_the_ack(self):
+        reach_agent = make_agent()
+        moonraker_result = {
+            "eventtime": 1234.5,
+            "status": {
+                "gcode_macro FAN_RPM": {"rpm": 4200},
+                "tmc2209 stepper_x": {"run_current": 0.8},
+            },
+        }
+        with mock.patch.object(
+            agent.HTTPClient, "get_json", return_value={"result": moonraker_result}
+        ) as get_json:
+            reach_agent._process_relay_command({
+                "requestId": "req-q",
+                "command": "query",
+                "params": {"objects": ["gcode_macro FAN_RPM", "tmc2209 stepper_x"]},
+            })
+        self.assertEqual(
+            get_json.call_args[0][0],
+            "http://127.0.0.1:7125/printer/objects/query?gcode_macro%20FAN_RPM&tmc2209%20stepper_x",
+        )
+        reach_agent.relay.push_command_result.assert_called_once_with(
+            request_id="req-q", status="completed", result={"result": moonraker_result}
+        )
+
+    def test_rejects_bad_or_too_many_objects(self):
+        reach_agent = make_agent()
+        too_many = [f"temperature_sensor s{n}" for n in range(agent._QUERY_MAX_OBJECTS + 1)]
+        for params in ({}, {"objects": []}, {"objects": ["ok", ""]}, {"objects": "extruder"}, {"objects": too_many}):
+            with self.subTest(params=params):
+                with mock.patch.object(agent.HTTPClient, "get_json") as get_json:
+                    result = reach_agent._execute_command("query", params)
+                self.assertEqual(result["errorCode"], "invalid_params")
+                get_json.assert_not_called()
+
+    def test_oversized_result_is_refused(self):
+        reach_agent = make_agent()
+        huge = {"status": {"gcode_macro DUMP": {"blob": "x" * agent._QUERY_MAX_RESPONSE_BYTES}}}
+        with mock.patch.object(agent.HTTPClient, "get_json", return_value={"result": huge}):
+            result = reach_agent._execute_command("query", {"objects": ["gcode_macro DUMP"]})
+        self.assertEqual(result["errorCode"], "response_too_large")
+
+
+class PowerCommandTests(unittest.TestCase):
+    _DEVICES = {"result": {"devices": [{"device": "printer", "status": "on", "type": "gpio"}]}}
+
+    def _run(self, params, routes):
+        """Execute a power command against Moonraker answers keyed by URL path."""
+        reach_agent = make_agent()
+        sent = []
+
+        def fake_open(req, timeout):
+            url = agent.urlparse(req.full_url)
+            sent.append((req.get_method(), url.path, url.query))
+            reply = routes[url.path]
+            if isinstance(reply, int):
+                raise HTTPError(req.full_url, reply, "Error", {}, None)
+            return json_response(reply)
+
+        with mock.patch.object(agent.HTTPClient, "open", side_effect=fake_open), \
+                self.assertLogs(agent.logger, "INFO"):
+            result = reach_agent._execute_command("power", params)
+        return result, sent
+
+    def test_switches_a_known_device(self):
+        result, sent = self._run({"device": "printer", "action": "off"}, {
+            "/machine/device_power/devices": self._DEVICES,
+            "/machine/device_power/device": {"result": {"printer": "off"}},
+        })
+        self.assertEqual(result, {"result": {"printer": "off"}})
+        self.assertEqual(sent[-1], ("POST", "/machine/device_power/device", "device=printer&action=off"))
+
+    def test_unknown_device_is_an_error_ack(self):
+        result, sent = self._run({"device": "lights", "action": "on"}, {
+            "/machine/device_power/devices": self._DEVICES,
+        })
+        self.assertEqual(result["errorCode"], "unknown_device")
+        self.assertEqual([path for _, path, _ in sent], ["/machine/device_power/devices"])
+
+    def test_missing_power_component_is_power_unavailable(self):
+        result, _ = self._run({"device": "printer", "action": "on"}, {
+            "/machine/device_power/devices": 404,
+        })
+        self.assertEqual(result["errorCode"], "power_unavailable")
+
+    def test_rejects_bad_params(self):
+        reach_agent = make_agent()
+        for params in ({"action": "on"}, {"device": "printer", "action": "reboot"}):
+            with self.subTest(params=params):
+                with mock.patch.object(agent.HTTPClient, "open") as open_:
+                    result = reach_agent._execute_command("power", params)
+                self.assertEqual(result["errorCode"], "invalid_params")
+                open_.assert_not_called()
+
+
+class ProgressFallbackTests(unittest.TestCase):
+    def _job(self, status, metadata=None):
+        """Job from a snapshot; `metadata` answers /server/files/metadata."""
+        def fake_get_json(url, *args, **kwargs):
+            if "/server/files/metadata" in url:
+                return {"result": metadata} if metadata is not None else None
+            return {"result": {"status": status}}
+
+        client = agent.MoonrakerClient("http://moonraker.local:7125")
+        client.sensor_objects = []
+        with mock.patch.object(agent.HTTPClient, "get_json", side_effect=fake_get_json) as get_json:
+            job = client.get_status()["job"]
+        return job, [c[0][0] for c in get_json.call_args_list]
+
+    PRINT_STATS = {"state": "printing", "filename": "benchy.gcode", "print_duration": 900, "total_duration": 1000}
+
+    def test_display_status_tier(self):
+        job, urls = self._job({
+            "print_stats": self.PRINT_STATS,
+            "display_status": {"progress": 0.42},
+            "virtual_sdcard": {"progress": 0.40},
+        })
+        self.assertAlmostEqual(job["progress"], 42.0)
+        self.assertFalse(any("/server/files/metadata" in url for url in urls))
+
+    def test_virtual_sdcard_tier(self):
+        job, _ = self._job({
+            "print_stats": self.PRINT_STATS,
+            "display_status": {"progress": None},
+            "virtual_sdcard": {"progress": 0.25},
+        })
+        self.assertAlmostEqual(job["progress"], 25.0)
+
+    def test_slicer_estimate_tier(self):
+        job, urls = self._job(
+            {"print_stats": self.PRINT_STATS},
+            metadata={"filename": "benchy.gcode", "estimated_time": 3600},
+        )
+        self.assertAlmostEqual(job["progress"], 25.0)
+        self.assertIn("http://moonraker.local:7125/server/files/metadata?filename=benchy.gcode", urls)
+
+    def test_no_source_gives_none_not_a_wall_clock_ratio(self):
+        for metadata in (None, {"filename": "benchy.gcode"}, {"estimated_time": 0}):
+            with self.subTest(metadata=metadata):
+                job, _ = self._job({"print_stats": self.PRINT_STATS}, metadata=metadata)
+                self.assertIsNone(job["progress"])
+
+    def test_clamped_to_100(self):
+        self.assertEqual(agent.derive_progress({}, {}, {"print_duration": 4000}, 3600), 100.0)
+
+    def test_estimate_is_looked_up_once_per_file(self):
+        client = agent.MoonrakerClient("http://moonraker.local:7125")
+        with mock.patch.object(
+            agent.HTTPClient, "get_json", return_value={"result": {"estimated_time": 3600}}
+        ) as get_json:
+            for filename in ("a.gcode", "a.gcode", "b.gcode"):
+                self.assertEqual(client.get_slicer_estimate(filename), 3600)
+            self.assertIsNone(client.get_slicer_estimate(None))
+        self.assertEqual(get_json.call_count, 2)
+
+
+class ExitReasonTests(unittest.TestCase):
+    def setUp(self):
+        patcher = mock.patch.object(agent, "_EXIT_NOTIFIERS", [])
+        self.notifiers = patcher.start()
+        self.addCleanup(patcher.stop)
+
+    def test_each_reason_maps_to_its_code_and_message(self):
+        expected = {
+            "STOPPED": 0, "FATAL_ERROR": 1, "CONFIG_ERROR": 2, "HEALTH_BIND_FAILED": 3,
+            "WATCHDOG": 4, "ONCE_FAILED": 5, "TOKEN_REVOKED": 6, "ALREADY_RUNNING": 7,
+            "UPDATED": 8, "UNINSTALLED": 9,
+        }
+        self.assertEqual({reason.name: reason.code for reason in agent.ExitReason}, expected)
+        for reason in agent.ExitReason:
+            with self.subTest(reason=reason.name):
+                with self.assertLogs(agent.logger, "INFO") as logs, \
+                        self.assertRaises(SystemExit) as raised:
+                    agent.exit_agent(reason, "detail")
+                self.assertEqual(raised.exception.code, reason.code)
+                self.assertEqual(
+                    logs.output[-1].split(":", 2)[2],
+                    f"Exiting: reason={reason.name.lower()} code={reason.code} "
+                    f"({reason.description}): detail",
+                )
+
+    def test_hard_exit_bypasses_sys_exit(self):
+        # os._exit never returns; stop there as it would.
+        with mock.patch.object(agent.os, "_exit", side_effect=RuntimeError("os._exit")) as os_exit, \
+                mock.patch.object(agent.logging, "shutdown"), \
+                self.assertLogs(agent.logger, "ERROR"), \
+                self.assertRaisesRegex(RuntimeError, "os._exit"):
+            agent.exit_agent(agent.ExitReason.UNINSTALLED, hard=True)
+        os_exit.assert_called_once_with(9)
+
+    def test_notifiers_hear_the_reason_before_exit(self):
+        heard = []
+        self.notifiers.append(lambda reason, detail: heard.append((reason, detail)))
+        with self.assertLogs(agent.logger, "ERROR"), self.assertRaises(SystemExit):
+            agent.exit_agent(agent.ExitReason.WATCHDOG, "stalled for 130s")
+        self.assertEqual(heard, [(agent.ExitReason.WATCHDOG, "stalled for 130s")])
+
+    def test_stuck_notifier_cannot_hold_up_the_exit(self):
+        release = threading.Event()
+        self.addCleanup(release.set)
+        self.notifiers.append(lambda reason, detail: release.wait())
+        with mock.patch.object(agent, "_EXIT_NOTICE_TIMEOUT_SECS", 0.05), \
+                self.assertLogs(agent.logger, "INFO"), self.assertRaises(SystemExit):
+            agent.exit_agent(agent.ExitReason.STOPPED)
+
+
+class DisconnectNoticeTests(unittest.TestCase):
+    def test_notice_carries_the_reason_in_one_attempt(self):
+        relay = make_relay()
+        with mock.patch.object(agent.HTTPClient, "post_json", return_value=None) as post_json:
+            relay.send_disconnect(agent.ExitReason.WATCHDOG, "stalled")
+        url, payload = post_json.call_args[0][:2]
+        self.assertEqual(url, "https://relay.example.com/api/reach-link/disconnect")
+        self.assertEqual(
+            {k: payload[k] for k in ("printerId", "reason", "code", "detail")},
+            {"printerId": "test-printer", "reason": "watchdog", "code": 4, "detail": "stalled"},
+        )
+        self.assertEqual(post_json.call_args[1]["max_retries"], 1)
+
+    def test_agent_skips_the_notice_once_revoked(self):
+        reach_agent = make_agent()
+        reach_agent.send_exit_notice(agent.ExitReason.STOPPED, "")
+        reach_agent.relay.send_disconnect.assert_called_once_with(agent.ExitReason.STOPPED, "")
+        reach_agent.relay.send_disconnect.reset_mock()
+        reach_agent.send_exit_notice(agent.ExitReason.TOKEN_REVOKED, "")
+        reach_agent.token_revoked = True
+        reach_agent.send_exit_notice(agent.ExitReason.STOPPED, "")
+        reach_agent.relay.send_disconnect.assert_not_called()
+
+
+class SharedCadenceTests(unittest.TestCase):
+    def test_heartbeat_on_every_nth_tick(self):
+        cadence = agent.SharedCadence(3)
+        self.assertEqual(
+            [cadence.next_tick() for _ in range(7)],
+            [True, False, False, True, False, False, True],
+        )
+
+    def test_loop_dispatches_both_requests_by_the_tick_ratio(self):
+        reach_agent = make_agent(REACH_LINK_SHARED_CADENCE="3", REACH_LINK_TELEMETRY_INTERVAL="10")
+        reach_agent.moonraker.get_status.side_effect = lambda: {"errors": []}
+        reach_agent.moonraker.get_job_queue.return_value = None
+        clock = [1000.0]
+        requests = []
+
+        def register(*args, **kwargs):
+            requests.append("register")
+            return {"ok": True}
+
+        def send(payload, last_ok=None):
+            requests.append("telemetry")
+            clock[0] += 10  # the next pass lands on the next tick
+            return True
+
+        reach_agent.relay.register_heartbeat.side_effect = register
+        reach_agent.relay.send_telemetry.side_effect = send
+        with mock.patch.object(agent.time, "time", side_effect=lambda: clock[0]):
+            run_loop(reach_agent, passes=7)
+
+        self.assertEqual(requests, [
+            "register", "telemetry", "telemetry", "telemetry",
+            "register", "telemetry", "telemetry", "telemetry",
+            "register", "telemetry",
+        ])
+
+
+class HttpErrorCategoryTests(unittest.TestCase):
+    def test_synthesized_failures_map_to_their_kind(self):
+        cases = {
+            "dns": URLError(socket.gaierror(-2, "Name or service not known")),
+            "tls": URLError(ssl.SSLError(1, "certificate verify failed")),
+            "refused": URLError(ConnectionRefusedError(111, "Connection refused")),
+            "timeout": socket.timeout("timed out"),
+            "reset": ConnectionResetError(104, "Connection reset by peer"),
+            "unreachable": URLError(OSError(errno.ENETUNREACH, "Network is unreachable")),
+            "client_error": HTTPError("http://relay/x", 404, "Not Found", {}, None),
+            "server_error": HTTPError("http://relay/x", 503, "Unavailable", {}, None),
+            "unknown": ValueError("odd"),
+        }
+        for kind, error in cases.items():
+            with self.subTest(kind=kind):
+                self.assertEqual(agent.classify_http_error(error), kind)
+                self.assertIn(kind, agent._HTTP_ERROR_HINTS)
+
+    def test_description_carries_kind_and_host_hint(self):
+        line = agent.describe_http_error(
+            "https://relay.example:8443/api", URLError(socket.gaierror(-2, "nope"))
+        )
+        self.assertTrue(line.startswith("error_kind=dns "), line)
+        self.assertIn("relay.example:8443", line)
+
+
+class MissingPrintStatsTests(unittest.TestCase):
+    def test_job_is_none_without_print_stats(self):
+        snapshot = moonraker_snapshot({
+            "extruder": {"temperature": 24.0, "target": 0.0},
+            "heater_bed": {"temperature": 23.5, "target": 0.0},
+        })
+        self.assertIsNone(snapshot["job"])
+        self.assertEqual(snapshot["temperatures"]["nozzle"], 24.0)
+
+    def test_payload_carries_a_null_job(self):
+        snapshot = moonraker_snapshot({"extruder": {"temperature": 24.0, "target": 0.0}})
+        payload = make_relay().build_telemetry_payload(snapshot)
+        self.assertIn("job", payload)
+        self.assertIsNone(payload["job"])
+
+
+if __name__ == "__main__":
+    unittest.main()
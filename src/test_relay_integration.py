@@ -0,0 +1,214 @@
+"""End-to-end tests of the agent loop against tools/mock-relay.py.
+
+The mock relay runs in-process on a free port and records every request, so
+these check what actually goes over the wire: JSON bodies, bearer auth, and
+the agent adopting the intervals the relay hands back.
+"""
+
+import argparse
+import contextlib
+import importlib.util
+import io
+import json
+import os
+import threading
+import unittest
+from http.server import ThreadingHTTPServer
+from unittest import mock
+
+from test_reach_link_agent import agent, make_config, run_loop
+
+_MOCK_RELAY_PATH = os.path.join(
+    os.path.dirname(os.path.dirname(os.path.abspath(__file__))), "tools", "mock-relay.py"
+)
+_spec = importlib.util.spec_from_file_location("mock_relay", _MOCK_RELAY_PATH)
+mock_relay = importlib.util.module_from_spec(_spec)
+_spec.loader.exec_module(mock_relay)
+
+_SNAPSHOT = {
+    "temperatures": {"nozzle": 210.4, "nozzleTarget": 210.0, "bed": 60.1, "bedTarget": 60.0},
+    "fans": {"partCooling": 1.0},
+    "motion": {"x": 120.0, "y": 95.5, "z": 3.2},
+    "job": {"filename": "benchy.gcode", "state": "printing", "progress": 42.0},
+    "system_health": {"cpuPercent": 12.5},
+    "errors": [],
+}
+
+
+class MockRelay:
+    """tools/mock-relay.py on a free local port, with its capture kept in memory."""
+
+    def __init__(self, **options):
+        args = argparse.Namespace(
+            token="test-token", next_check_in=45, next_data_interval=15, fail_status=0
+        )
+        for name, value in options.items():
+            setattr(args, name, value)
+        self.capture = io.StringIO()
+
+        class QuietHandler(mock_relay.make_handler(args, self.capture)):
+            def log_message(self, format, *args):
+                pass
+
+        self.httpd = ThreadingHTTPServer(("127.0.0.1", 0), QuietHandler)
+        self.url = f"http://127.0.0.1:{self.httpd.server_address[1]}"
+
+    def __enter__(self):
+        threading.Thread(target=self.httpd.serve_forever, daemon=True).start()
+        return self
+
+    def __exit__(self, *exc):
+        self.httpd.shutdown()
+        self.httpd.server_close()
+
+    def requests(self, path=None):
+        records = [json.loads(line) for line in self.capture.getvalue().splitlines()]
+        return [r for r in records if path is None or r["path"] == path]
+
+
+def make_relay_agent(relay_url: str, **env) -> "agent.ReachLinkAgent":
+    """Agent with a real relay client pointed at `relay_url` and a mocked Moonraker."""
+    env = dict({
+        "REACH_LINK_RELAY": relay_url,
+        "REACH_LINK_WATCHDOG_MULTIPLIER": "0",
+        "REACH_LINK_HISTORY_INTERVAL": "0",
+        "REACH_LINK_FILAMENT_INTERVAL": "0",
+    }, **env)
+    reach_agent = agent.ReachLinkAgent(make_config(**env))
+    reach_agent.moonraker = mock.create_autospec(agent.MoonrakerClient, instance=True)
+    reach_agent.moonraker.get_status.side_effect = lambda: json.loads(json.dumps(_SNAPSHOT))
+    reach_agent.moonraker.get_errors.return_value = []
+    reach_agent.moonraker.get_printer_limits.return_value = None
+    reach_agent.moonraker.get_machine_hint.return_value = None
+    reach_agent.moonraker.get_job_queue.return_value = None
+    return reach_agent
+
+
+class MockRelayIntegrationTests(unittest.TestCase):
+    def setUp(self):
+        patcher = mock.patch.multiple(agent.HTTPClient, ssl_context=None, pool=None)
+        patcher.start()
+        self.addCleanup(patcher.stop)
+
+    def test_success_sends_json_with_bearer_auth_and_adopts_intervals(self):
+        with MockRelay() as relay:
+            reach_agent = make_relay_agent(relay.url)
+            sleeps = run_loop(reach_agent)
+
+        self.assertEqual(sleeps, [1])
+        for record in relay.requests():
+            self.assertEqual(record["authorization"], "Bearer test-token", record["path"])
+
+        (register,) = relay.requests("/api/reach-link/register")
+        self.assertEqual(register["body"]["printerId"], "test-printer")
+        self.assertEqual(register["body"]["token"], "test-token")
+        self.assertEqual(register["body"]["version"], agent.AGENT_VERSION)
+
+        (telemetry,) = relay.requests("/api/reach-link/printer-data")
+        body = telemetry["body"]
+        self.assertEqual(body["printerId"], "test-printer")
+        self.assertEqual(body["seq"], 1)
+        self.assertEqual(body["temperatures"]["nozzle"], 210.4)
+        self.assertEqual(body["job"]["filename"], "benchy.gcode")
+        self.assertEqual(body["errors"], [])
+
+        self.assertEqual(reach_agent.config.heartbeat_interval, 45)
+        self.assertEqual(reach_agent.config.telemetry_interval, 15)
+        self.assertIsNotNone(reach_agent.last_heartbeat_ok)
+        self.assertIsNotNone(reach_agent.last_telemetry_ok)
+
+    def test_relay_500_is_retried_and_the_loop_keeps_running(self):
+        with MockRelay(fail_status=500) as relay, \
+                mock.patch.object(agent, "_RELAY_RETRY_DELAY_SECS", 0):
+            reach_agent = make_relay_agent(relay.url, REACH_LINK_RELAY_RETRIES="1")
+            sleeps = run_loop(reach_agent)
+
+        # One retry each, then the loop carries on to its normal 1s sleep.
+        self.assertEqual(len(relay.requests("/api/reach-link/register")), 2)
+        self.assertEqual(len(relay.requests("/api/reach-link/printer-data")), 2)
+        self.assertEqual(sleeps, [1])
+        self.assertIsNone(reach_agent.last_heartbeat_ok)
+        self.assertIsNone(reach_agent.last_telemetry_ok)
+        self.assertFalse(reach_agent.token_revoked)
+        # Intervals stay at their configured values.
+        self.assertEqual(reach_agent.config.heartbeat_interval, 30)
+        self.assertEqual(reach_agent.config.telemetry_interval, 10)
+
+    def test_wrong_token_is_token_revocation(self):
+        with MockRelay(token="other-token") as relay:
+            reach_agent = make_relay_agent(relay.url)
+            run_loop(reach_agent, passes=5)
+
+        self.assertTrue(reach_agent.token_revoked)
+        self.assertEqual(len(relay.requests("/api/reach-link/register")), 1)
+        self.assertEqual(relay.requests("/api/reach-link/printer-data"), [])
+
+    def test_basic_auth_sends_the_token_as_x_reach_token(self):
+        with MockRelay() as relay:
+            reach_agent = make_relay_agent(
+                relay.url,
+                REACH_LINK_RELAY_BASIC_USER="proxy-user",
+                REACH_LINK_RELAY_BASIC_PASS="proxy-pass",
+            )
+            run_loop(reach_agent)
+
+        basic = agent.HTTPClient.basic_auth_header("proxy-user", "proxy-pass")
+        self.assertTrue(relay.requests())
+        for record in relay.requests():
+            self.assertEqual(record["authorization"], basic, record["path"])
+        self.assertIsNotNone(reach_agent.last_heartbeat_ok)
+        self.assertFalse(reach_agent.token_revoked)
+
+    def test_request_id_header_matches_the_logged_id(self):
+        with MockRelay() as relay, self.assertLogs(agent.logger, level="DEBUG") as logs:
+            run_loop(make_relay_agent(relay.url))
+
+        (register,) = relay.requests("/api/reach-link/register")
+        (telemetry,) = relay.requests("/api/reach-link/printer-data")
+        self.assertNotEqual(register["requestId"], telemetry["requestId"])
+        for record, message in (
+            (register, "Heartbeat registered"),
+            (telemetry, "Telemetry sent successfully"),
+        ):
+            self.assertTrue(record["requestId"])
+            (line,) = [line for line in logs.output if message in line]
+            self.assertTrue(line.endswith(f"request_id={record['requestId']}"), line)
+
+
+class OnceModeTests(unittest.TestCase):
+    def setUp(self):
+        patcher = mock.patch.multiple(agent.HTTPClient, ssl_context=None, pool=None)
+        patcher.start()
+        self.addCleanup(patcher.stop)
+
+    def _run_once(self, relay, **env):
+        reach_agent = make_relay_agent(relay.url, **env)
+        stdout = io.StringIO()
+        with contextlib.redirect_stdout(stdout):
+            ok = reach_agent.run_once()
+        return ok, json.loads(stdout.getvalue())
+
+    def test_once_registers_sends_one_sample_and_prints_the_replies(self):
+        with MockRelay() as relay:
+            ok, printed = self._run_once(relay)
+
+        self.assertTrue(ok)
+        self.assertEqual(len(relay.requests("/api/reach-link/register")), 1)
+        (telemetry,) = relay.requests("/api/reach-link/printer-data")
+        self.assertEqual(telemetry["body"]["seq"], 1)
+        self.assertEqual(printed["printerId"], "test-printer")
+        self.assertEqual(printed["register"]["nextCheckIn"], 45)
+        self.assertEqual(printed["telemetry"]["nextDataInterval"], 15)
+
+    def test_once_fails_when_the_relay_does(self):
+        with MockRelay(fail_status=500) as relay:
+            ok, printed = self._run_once(relay, REACH_LINK_RELAY_RETRIES="0")
+
+        self.assertFalse(ok)
+        self.assertIsNone(printed["register"])
+        self.assertIsNone(printed["telemetry"])
+
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,126 @@
+#!/usr/bin/env python3
+"""
+Mock Reach relay for local development.
+
+Serves the relay endpoints the agent talks to, records every JSON body it
+receives (one JSON object per line, to stdout or a capture file), and answers
+with canned intervals so agent behaviour can be exercised end-to-end without
+a real relay:
+
+    python3 tools/mock-relay.py --port 8787 --token test-token
+    REACH_LINK_RELAY=http://127.0.0.1:8787 REACH_LINK_TOKEN=test-token \
+        REACH_LINK_PRINTER_ID=test-printer python3 src/reach-link-agent.py
+
+Use --fail-status 500 to make register/telemetry fail and watch the agent's
+retry and backoff handling.
+"""
+
+import argparse
+import json
+import sys
+import threading
+import time
+from http.server import BaseHTTPRequestHandler, ThreadingHTTPServer
+
+
+def make_handler(args, capture):
+    lock = threading.Lock()
+
+    class Handler(BaseHTTPRequestHandler):
+        protocol_version = "HTTP/1.1"
+
+        def do_GET(self):
+            if self.path == "/api/reach-link/version":
+                # Report an old version so the agent never tries to self-update.
+                self._reply(200, {"version": "0.0.0"})
+            else:
+                self._reply(404, {"error": "not found"})
+
+        def do_POST(self):
+            length = int(self.headers.get("Content-Length") or 0)
+            raw = self.rfile.read(length) if length else b""
+            try:
+                body = json.loads(raw.decode("utf-8")) if raw else None
+            except ValueError:
+                body = raw.decode("utf-8", errors="replace")
+
+            with lock:
+                capture.write(json.dumps({
+                    "ts": int(time.time() * 1000),
+                    "path": self.path,
+                    "authorization": self.headers.get("Authorization"),
+                    "requestId": self.headers.get("X-Request-Id"),
+                    "body": body,
+                }) + "\n")
+                capture.flush()
+
+            # Behind a Basic auth proxy the agent sends its token as X-Reach-Token
+            token = self.headers.get("X-Reach-Token")
+            if token is None:
+                token = (self.headers.get("Authorization") or "").partition("Bearer ")[2]
+            if args.token and token != args.token:
+                self._reply(401, {"error": "invalid token"})
+                return
+
+            if self.path == "/api/reach-link/register":
+                if args.fail_status:
+                    self._reply(args.fail_status, {"error": "injected failure"})
+                else:
+                    self._reply(200, {"ok": True, "nextCheckIn": args.next_check_in})
+            elif self.path == "/api/reach-link/printer-data":
+                if args.fail_status:
+                    self._reply(args.fail_status, {"error": "injected failure"})
+                else:
+                    self._reply(200, {"ok": True, "nextDataInterval": args.next_data_interval})
+            elif self.path == "/api/reach-link/commands/pull":
+                self._reply(200, {"command": None})
+            elif self.path in (
+                "/api/reach-link/commands/push",
+                "/api/reach-link/webcam-snapshot",
+                "/api/reach-link/disconnect",
+            ):
+                self._reply(200, {"ok": True})
+            else:
+                self._reply(404, {"error": "not found"})
+
+        def _reply(self, code, body):
+            data = json.dumps(body).encode("utf-8")
+            self.send_response(code)
+            self.send_header("Content-Type", "application/json")
+            if self.headers.get("X-Request-Id"):
+                self.send_header("X-Request-Id", self.headers["X-Request-Id"])
+            self.send_header("Content-Length", str(len(data)))
+            self.end_headers()
+            self.wfile.write(data)
+
+        def log_message(self, format, *args):
+            print(f"[mock-relay] {self.address_string()} {format % args}", file=sys.stderr)
+
+    return Handler
+
+
+def main():
+    parser = argparse.ArgumentParser(description="Mock Reach relay for local agent testing")
+    parser.add_argument("--bind", default="127.0.0.1")
+    parser.add_argument("--port", type=int, default=8787)
+    parser.add_argument("--token", default="", help="require this bearer token (default: accept any)")
+    parser.add_argument("--next-check-in", type=int, default=30)
+    parser.add_argument("--next-data-interval", type=int, default=10)
+    parser.add_argument("--fail-status", type=int, default=0,
+                        help="answer register/telemetry with this HTTP status (e.g. 500)")
+    parser.add_argument("--capture", help="append received requests to this JSONL file instead of stdout")
+    args = parser.parse_args()
+
+    capture = open(args.capture, "a", encoding="utf-8") if args.capture else sys.stdout
+    server = ThreadingHTTPServer((args.bind, args.port), make_handler(args, capture))
+    print(f"[mock-relay] listening on http://{args.bind}:{args.port}", file=sys.stderr)
+    try:
+        server.serve_forever()
+    except KeyboardInterrupt:
+        pass
+    finally:
+        server.server_close()
+
+
+if __name__ == "__main__":
+    main()